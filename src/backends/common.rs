@@ -1,93 +1,527 @@
-use crate::model::{AppData, ConfigResult, HeadInfo};
-use std::io::Write;
-use std::process;
-use wayland_client::{EventQueue, QueueHandle};
+use crate::commands::commands::CommandError;
+use crate::configuration::{Configuration, HeadConfiguration, ModeSetting};
+use crate::handles::OUTPUT_MANAGER_INTERFACE_NAME;
+use crate::head_printer::print_changes;
+use crate::history::{HistoryEntry, record_history_entry};
+use crate::model::{AppData, ConfigResult, HeadInfo, HeadState, PropertyChange};
+use crate::store::add_head_states;
+use crate::trace;
+use rustix::event::{PollFd, PollFlags, Timespec, poll};
+use rustix::io::Errno;
+use std::fmt::Display;
+use std::io::{ErrorKind, IsTerminal, Write};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
+use wayland_client::backend::WaylandError;
+use wayland_client::{EventQueue, Proxy, QueueHandle};
+use wayland_protocols_wlr::output_management::v1::client::zwlr_output_configuration_head_v1::REQ_SET_ADAPTIVE_SYNC_SINCE;
 use wayland_protocols_wlr::output_management::v1::client::zwlr_output_configuration_v1::ZwlrOutputConfigurationV1;
 
 pub fn apply(
     state: &mut AppData,
     event_queue: &mut EventQueue<AppData>,
-    configure: impl FnOnce(&ZwlrOutputConfigurationV1, &QueueHandle<AppData>),
-) -> ConfigResult {
-    let qh = event_queue.handle();
-    let manager = state.manager.as_ref().expect("output manager not bound");
-    let serial: u32 = state.config_serial.unwrap();
-    let configuration = manager.create_configuration(serial, &qh, ());
+    configuration: &Configuration,
+) -> Result<ConfigResult, CommandError> {
+    // Within a merged batch, the changes are only sent once every command ran
+    if let Some(batch_configuration) = state.batch_configuration.as_mut() {
+        batch_configuration.merge(configuration);
+        return Ok(ConfigResult::Succeeded);
+    }
 
-    state.config_result = None;
-    configure(&configuration, &qh);
-    configuration.apply();
+    let mut configuration = configuration.clone();
+    let mut retries = 0;
+    loop {
+        let serial = state.config_serial;
+        let result = apply_once(state, event_queue, &configuration)?;
 
-    while state.config_result.is_none() {
-        event_queue.blocking_dispatch(state).unwrap();
+        if result != ConfigResult::Cancelled || retries >= state.options.retries {
+            return Ok(result);
+        }
+
+        retries += 1;
+        eprintln!(
+            "The displays changed before the configuration was applied, retrying ({}/{})",
+            retries, state.options.retries
+        );
+
+        // The configuration must be sent again with the serial of the new state
+        dispatch_until(
+            state,
+            event_queue,
+            "send the new state of the displays",
+            |state| state.config_serial != serial,
+        )?;
+        configuration = resolve_heads(state, &configuration)?;
     }
+}
 
-    let first_result = state.config_result.unwrap();
-    configuration.destroy();
+/// Adds states to a state file once the configuration holding the change is applied, which is only
+/// at the end of a merged batch.
+pub fn remember_head_states(state: &mut AppData, file_name: &'static str, states: Vec<HeadState>) {
+    if state.batch_configuration.is_some() {
+        state.batch_saved_states.push((file_name, states));
+        return;
+    }
 
-    reposition_displays_to_origin(state, event_queue, &qh);
+    if let Err(error) = add_head_states(file_name, &states) {
+        eprintln!("Unable to save the display settings: {}", error);
+    }
+}
 
-    first_result
+/// Finds the heads of a configuration again once the displays changed, by object when it still exists
+/// or else by identity, since a display reconnected is advertised as a new head.
+fn resolve_heads(
+    state: &AppData,
+    configuration: &Configuration,
+) -> Result<Configuration, CommandError> {
+    configuration
+        .resolve_heads(|head| {
+            state
+                .heads
+                .get(&head.head.id())
+                .or_else(|| state.find_head_for_state(&HeadState::from(head)))
+                .cloned()
+        })
+        .map_err(CommandError::InvalidRequest)
 }
 
-fn reposition_displays_to_origin(
+fn apply_once(
     state: &mut AppData,
     event_queue: &mut EventQueue<AppData>,
-    qh: &QueueHandle<AppData>,
-) {
-    event_queue.roundtrip(state).unwrap();
+    configuration: &Configuration,
+) -> Result<ConfigResult, CommandError> {
+    let prepared = prepare_configuration(state, configuration)?;
+    let before = head_states(state);
 
-    let enabled_heads: Vec<&HeadInfo> = state
-        .heads
-        .values()
-        .filter(|head| head.enabled && head.position_x.is_some() && head.position_y.is_some())
+    let planned: Vec<HeadState> = prepared
+        .heads()
+        .iter()
+        .map(HeadConfiguration::resulting_state)
         .collect();
 
-    if let Some(closest_to_origin) = enabled_heads
+    if state.options.plan {
+        println!("Plan:");
+        print_state_changes(&before, &planned, "No change planned");
+    }
+
+    // Even an identical configuration can make the compositor do a modeset and blank the displays
+    if is_unchanged(&before, &planned) && !state.options.force_apply {
+        println!("Already in requested state");
+        return Ok(ConfigResult::Succeeded);
+    }
+
+    check_displays_left_on(state, &prepared)?;
+
+    let result = send_configuration(state, event_queue, &prepared, false)?;
+
+    if result == ConfigResult::Succeeded {
+        // The compositor sends the new state of the heads once it is done applying
+        roundtrip(state, event_queue)?;
+        let after = head_states(state);
+
+        if state.options.plan {
+            println!("Applied:");
+        }
+        print_state_changes(&before, &after, "No display changed");
+
+        let entry = HistoryEntry::new(&before, &after);
+        if !entry.is_empty()
+            && let Err(error) = record_history_entry(entry)
+        {
+            eprintln!("Unable to record the change in the history: {}", error);
+        }
+    }
+
+    if result == ConfigResult::Succeeded {
+        check_applied(state, &prepared)?;
+    }
+
+    Ok(result)
+}
+
+/// Asks the compositor to validate a configuration without applying it.
+pub fn test(
+    state: &mut AppData,
+    event_queue: &mut EventQueue<AppData>,
+    configuration: &Configuration,
+) -> Result<ConfigResult, CommandError> {
+    let prepared = match &state.batch_configuration {
+        Some(batch_configuration) => {
+            let mut merged = batch_configuration.clone();
+            merged.merge(configuration);
+            prepare_configuration(state, &merged)?
+        }
+        None => prepare_configuration(state, configuration)?,
+    };
+
+    send_configuration(state, event_queue, &prepared, true)
+}
+
+/// Compares the heads re-read after applying with the requested settings, since the compositor may
+/// clamp or round some of them. The differences are warnings, or an error under `--strict`.
+fn check_applied(state: &AppData, prepared: &Configuration) -> Result<(), CommandError> {
+    let differences = applied_differences(state, prepared);
+    if differences.is_empty() {
+        return Ok(());
+    }
+
+    if state.options.strict {
+        return Err(CommandError::NotApplied(differences.join("\n")));
+    }
+    for difference in differences {
+        eprintln!("Warning: {}", difference);
+    }
+
+    Ok(())
+}
+
+/// The settings of the configuration that the heads re-read after applying it do not have.
+pub fn applied_differences(state: &AppData, prepared: &Configuration) -> Vec<String> {
+    let mut differences = vec![];
+    for head_configuration in prepared.heads() {
+        let Some(head) = state.heads.get(&head_configuration.head.head.id()) else {
+            continue;
+        };
+        let actual = HeadState::from(head);
+        let requested = head_configuration.applied_to(actual.clone());
+
+        for change in requested.changes_to(&actual) {
+            differences.push(format!(
+                "{} of display {} is {} instead of the requested {}",
+                change.property, actual.name, change.after, change.before
+            ));
+        }
+    }
+
+    differences
+}
+
+/// Refuses a configuration that leaves every display off, unless forced or confirmed.
+fn check_displays_left_on(state: &AppData, prepared: &Configuration) -> Result<(), CommandError> {
+    if leaves_a_display_on(prepared) || state.options.force {
+        return Ok(());
+    }
+
+    confirm(
+        "You are about to power off all your displays.\nProceed ? (Y/n)",
+        "Refusing to power off all the displays",
+    )
+}
+
+/// Whether at least one display is still on once the configuration completed by `prepare_configuration`
+/// is applied.
+pub fn leaves_a_display_on(prepared: &Configuration) -> bool {
+    prepared.heads().iter().any(|head| head.enabled)
+}
+
+/// Whether every head would stay as it is, within the rounding of the values the compositor reports.
+fn is_unchanged(before: &[HeadState], planned: &[HeadState]) -> bool {
+    planned.iter().all(|planned_state| {
+        before
+            .iter()
+            .find(|state| state.name == planned_state.name)
+            .is_some_and(|state| state.changes_to(planned_state).is_empty())
+    })
+}
+
+pub fn head_states(state: &AppData) -> Vec<HeadState> {
+    state.heads.values().map(HeadState::from).collect()
+}
+
+/// Prints, per display, the properties that differ between the two states.
+fn print_state_changes(before: &[HeadState], after: &[HeadState], unchanged: &str) {
+    let mut changes: Vec<(String, Vec<PropertyChange>)> = after
         .iter()
-        .copied()
-        .min_by_key(|head| head.distance_to_origin())
+        .filter_map(|after_state| {
+            let before_state = before.iter().find(|state| state.name == after_state.name)?;
+            let head_changes = before_state.changes_to(after_state);
+
+            (!head_changes.is_empty()).then(|| (after_state.name.clone(), head_changes))
+        })
+        .collect();
+
+    if changes.is_empty() {
+        println!("{}", unchanged);
+        return;
+    }
+
+    changes.sort_by(|(name, _), (other_name, _)| name.cmp(other_name));
+    print_changes(changes)
+}
+
+/// Completes the configuration with the heads it leaves out and moves the layout to the origin, so that
+/// everything is sent in a single configuration.
+pub fn prepare_configuration(
+    state: &AppData,
+    configuration: &Configuration,
+) -> Result<Configuration, CommandError> {
+    if !state.supports(REQ_SET_ADAPTIVE_SYNC_SINCE)
+        && let Some(head_configuration) = configuration
+            .heads()
+            .iter()
+            .find(|head_configuration| head_configuration.adaptive_sync.is_some())
     {
-        if closest_to_origin.position_x.unwrap() != 0 || closest_to_origin.position_y.unwrap() != 0
-        {
-            let manager = state.manager.as_ref().expect("output manager not bound");
-            let serial: u32 = state.config_serial.unwrap();
-            let configuration = manager.create_configuration(serial, &qh, ());
-
-            state.config_result = None;
-            for head in enabled_heads {
-                let head_config = configuration.enable_head(&head.head, &qh, ());
-                let x = head.position_x.unwrap() - closest_to_origin.position_x.unwrap();
-                let y = head.position_y.unwrap() - closest_to_origin.position_y.unwrap();
-                head_config.set_position(x, y);
-            }
+        return Err(CommandError::InvalidRequest(format!(
+            "Adaptive sync cannot be set on display {}, the compositor only supports version {} of {}",
+            head_configuration.head.name.clone().unwrap_or_default(),
+            state.manager_version,
+            OUTPUT_MANAGER_INTERFACE_NAME
+        )));
+    }
 
-            configuration.apply();
-            while state.config_result.is_none() {
-                event_queue.blocking_dispatch(state).unwrap();
-            }
-            configuration.destroy();
+    let mut prepared = configuration.clone();
+    let heads: Vec<HeadInfo> = state.heads.values().cloned().collect();
+    prepared.include_unchanged(&heads);
+
+    if !state.options.no_normalize {
+        prepared
+            .normalize(state.options.anchor.as_deref())
+            .map_err(CommandError::InvalidRequest)?;
+    }
+
+    Ok(prepared)
+}
+
+/// Sends a configuration completed by `prepare_configuration` and waits for the compositor answer.
+pub fn send_configuration(
+    state: &mut AppData,
+    event_queue: &mut EventQueue<AppData>,
+    prepared: &Configuration,
+    test_only: bool,
+) -> Result<ConfigResult, CommandError> {
+    let qh = event_queue.handle();
+    let manager = state.manager.as_ref().expect("output manager not bound");
+    let serial: u32 = state.config_serial.unwrap();
+    let wl_configuration = manager.create_configuration(serial, &qh, ());
+    trace::request(&manager.id(), || {
+        format!(
+            "create_configuration(new {}, {})",
+            wl_configuration.id(),
+            serial
+        )
+    });
+
+    state.config_result = None;
+    for head_configuration in prepared.heads() {
+        configure_head(&wl_configuration, &qh, head_configuration);
+    }
+    if test_only {
+        wl_configuration.test();
+        trace::request(&wl_configuration.id(), || String::from("test()"));
+    } else {
+        wl_configuration.apply();
+        trace::request(&wl_configuration.id(), || String::from("apply()"));
+    }
+
+    dispatch_until(state, event_queue, "answer the configuration", |state| {
+        state.config_result.is_some()
+    })?;
+
+    let result = state.config_result.unwrap();
+    trace::request(&wl_configuration.id(), || String::from("destroy()"));
+    wl_configuration.destroy();
+
+    Ok(result)
+}
+
+fn configure_head(
+    config: &ZwlrOutputConfigurationV1,
+    qh: &QueueHandle<AppData>,
+    head_configuration: &HeadConfiguration,
+) {
+    let head = &head_configuration.head.head;
+    if !head_configuration.enabled {
+        config.disable_head(head);
+        trace::request(&config.id(), || format!("disable_head({})", head.id()));
+        return;
+    }
+
+    let head_config = config.enable_head(head, qh, ());
+    trace::request(&config.id(), || {
+        format!("enable_head(new {}, {})", head_config.id(), head.id())
+    });
+
+    match &head_configuration.mode {
+        Some(ModeSetting::Advertised(mode)) => {
+            let wl_mode = mode.mode.as_ref().unwrap();
+            head_config.set_mode(wl_mode);
+            trace::request(&head_config.id(), || {
+                format!(
+                    "set_mode({}) {}x{}@{}",
+                    wl_mode.id(),
+                    mode.width,
+                    mode.height,
+                    mode.rate
+                )
+            });
+        }
+        Some(ModeSetting::Custom(mode)) => {
+            head_config.set_custom_mode(mode.width, mode.height, mode.rate * 1000);
+            trace::request(&head_config.id(), || {
+                format!(
+                    "set_custom_mode({}, {}, {})",
+                    mode.width,
+                    mode.height,
+                    mode.rate * 1000
+                )
+            });
         }
+        None => {}
+    }
+    if let Some((x, y)) = head_configuration.position {
+        head_config.set_position(x, y);
+        trace::request(&head_config.id(), || format!("set_position({}, {})", x, y));
+    }
+    if let Some(scale) = head_configuration.scale {
+        head_config.set_scale(scale);
+        trace::request(&head_config.id(), || format!("set_scale({})", scale));
+    }
+    if let Some(transform) = head_configuration.transform {
+        head_config.set_transform(transform);
+        trace::request(&head_config.id(), || {
+            format!("set_transform({:?})", transform)
+        });
+    }
+    if let Some(adaptive_sync) = head_configuration.adaptive_sync {
+        head_config.set_adaptive_sync(adaptive_sync);
+        trace::request(&head_config.id(), || {
+            format!("set_adaptive_sync({:?})", adaptive_sync)
+        });
     }
 }
 
-pub fn handle_result(config_result: ConfigResult, success: &str, fail: &str) {
+/// Turns the result of `apply` into the command result, the changes were already reported by `apply`.
+pub fn handle_result(config_result: ConfigResult, fail: &str) -> Result<(), CommandError> {
     match config_result {
-        ConfigResult::Succeeded => {
-            println!("{}", success)
+        ConfigResult::Succeeded => Ok(()),
+        ConfigResult::Failed => Err(CommandError::ConfigurationFailed(fail.to_string())),
+        ConfigResult::Cancelled => Err(CommandError::ConfigurationCancelled),
+    }
+}
+
+/// Dispatches the compositor events until the condition holds, failing with a timeout error when it takes
+/// longer than `--timeout`. `action` tells what the compositor was expected to do in the error.
+pub fn dispatch_until(
+    state: &mut AppData,
+    event_queue: &mut EventQueue<AppData>,
+    action: &str,
+    condition: impl Fn(&AppData) -> bool,
+) -> Result<(), CommandError> {
+    let timeout = state.options.protocol_timeout();
+    let deadline = Instant::now() + timeout;
+
+    while !condition(state) {
+        if !dispatch_before(state, event_queue, Some(deadline))? {
+            return Err(CommandError::Timeout(format!(
+                "The compositor did not {} within {}s",
+                action,
+                timeout.as_secs_f64()
+            )));
         }
-        ConfigResult::Failed => {
-            eprintln!("{}", fail);
-            process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// Waits until the compositor processed every request sent so far and sent the resulting events.
+pub fn roundtrip(
+    state: &mut AppData,
+    event_queue: &mut EventQueue<AppData>,
+) -> Result<(), CommandError> {
+    let done = Arc::new(AtomicBool::new(false));
+    let display = state.connection.display();
+    let callback = display.sync(&event_queue.handle(), done.clone());
+    trace::request(&display.id(), || format!("sync(new {})", callback.id()));
+
+    dispatch_until(state, event_queue, "answer", |_| {
+        done.load(Ordering::Relaxed)
+    })
+}
+
+/// Waits for the compositor events and dispatches them, like `blocking_dispatch` but giving up at the
+/// deadline when one is given.
+///
+/// Returns `false` when the deadline passed before any event arrived.
+pub fn dispatch_before(
+    state: &mut AppData,
+    event_queue: &mut EventQueue<AppData>,
+    deadline: Option<Instant>,
+) -> Result<bool, CommandError> {
+    let connection_lost = |error: &dyn Display| CommandError::ConnectionLost(error.to_string());
+
+    // Events may already be queued, in which case the connection must not be read
+    if event_queue
+        .dispatch_pending(state)
+        .map_err(|error| connection_lost(&error))?
+        > 0
+    {
+        return Ok(true);
+    }
+    event_queue
+        .flush()
+        .map_err(|error| connection_lost(&error))?;
+
+    let Some(guard) = event_queue.prepare_read() else {
+        event_queue
+            .dispatch_pending(state)
+            .map_err(|error| connection_lost(&error))?;
+        return Ok(true);
+    };
+
+    let timeout = match deadline {
+        Some(deadline) => {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Ok(false);
+            }
+            // Too far away to be represented, which is as good as no deadline
+            Timespec::try_from(remaining).ok()
         }
-        ConfigResult::Cancelled => {
-            eprintln!("Configuration cancelled",);
-            process::exit(1);
+        None => None,
+    };
+
+    let fd = guard.connection_fd();
+    let mut fds = [PollFd::new(&fd, PollFlags::IN)];
+    loop {
+        match poll(&mut fds, timeout.as_ref()) {
+            Ok(0) => return Ok(false),
+            Ok(_) => break,
+            Err(Errno::INTR) => continue,
+            Err(error) => return Err(connection_lost(&error)),
         }
     }
+
+    match guard.read() {
+        Ok(_) => {}
+        Err(WaylandError::Io(error)) if error.kind() == ErrorKind::WouldBlock => {}
+        Err(error) => return Err(connection_lost(&error)),
+    }
+    event_queue
+        .dispatch_pending(state)
+        .map_err(|error| connection_lost(&error))?;
+
+    Ok(true)
 }
 
-pub fn prompt(text: &str) -> String {
+/// Asks a yes or no question, answering no aborts the command. When stdin is not a terminal nobody can
+/// answer, so the command is refused with the reason given instead of waiting for input.
+pub fn confirm(question: &str, refusal: &str) -> Result<(), CommandError> {
+    if !std::io::stdin().is_terminal() {
+        return Err(CommandError::InvalidRequest(format!(
+            "{}, use --force to proceed anyway",
+            refusal
+        )));
+    }
+
+    if prompt(question).to_lowercase() != "y" {
+        return Err(CommandError::Aborted);
+    }
+
+    Ok(())
+}
+
+fn prompt(text: &str) -> String {
     print!("{} ", text);
     std::io::stdout().flush().expect("Oups, stdout error");
 
@@ -98,3 +532,60 @@ pub fn prompt(text: &str) -> String {
 
     response.trim_end().to_string()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::HeadModeInput;
+    use wayland_client::protocol::wl_output::Transform;
+
+    fn state(name: &str, scale: f64) -> HeadState {
+        HeadState {
+            name: name.to_string(),
+            make: None,
+            model: None,
+            serial: None,
+            enabled: true,
+            mode: Some(HeadModeInput {
+                width: 2560,
+                height: 1440,
+                rate: 59,
+            }),
+            refresh: Some(59951),
+            position: Some((0, 0)),
+            scale: Some(scale),
+            transform: Some(Transform::Normal),
+            adaptive_sync: None,
+        }
+    }
+
+    /// The scale as reported by the compositor once applied, a wl_fixed in steps of 1/256.
+    fn reported(scale: f64) -> f64 {
+        (scale * 256.0) as i32 as f64 / 256.0
+    }
+
+    #[test]
+    fn requesting_the_current_scale_is_unchanged() {
+        for scale in [1.1, 1.25, 1.5] {
+            let before = [state("DP-1", reported(scale)), state("HDMI-A-1", 1.0)];
+            let planned = [state("DP-1", scale), state("HDMI-A-1", 1.0)];
+
+            assert!(is_unchanged(&before, &planned), "{}", scale);
+        }
+    }
+
+    #[test]
+    fn requesting_another_scale_is_a_change() {
+        for (current, requested) in [(1.1, 1.25), (1.25, 1.5), (1.5, 1.1)] {
+            let before = [state("DP-1", reported(current))];
+            let planned = [state("DP-1", requested)];
+
+            assert!(
+                !is_unchanged(&before, &planned),
+                "{} to {}",
+                current,
+                requested
+            );
+        }
+    }
+}
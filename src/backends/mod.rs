@@ -1,4 +1,4 @@
 #[cfg(feature = "wlroot")]
-mod wlroot;
+pub mod wlroot;
 
-mod common;
+pub(crate) mod common;
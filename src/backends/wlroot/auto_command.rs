@@ -0,0 +1,50 @@
+use crate::backends::common::{apply, handle_result};
+use crate::backends::wlroot::connect_trait::WaylandCommand;
+use crate::commands::commands::{AutoCommand, CommandError};
+use crate::configuration::Configuration;
+use crate::model::{AppData, HeadInfo};
+use wayland_client::EventQueue;
+use wayland_client::protocol::wl_output::Transform;
+
+impl WaylandCommand for AutoCommand {
+    fn run(
+        &self,
+        event_queue: &mut EventQueue<AppData>,
+        state: &mut AppData,
+    ) -> Result<(), CommandError> {
+        // Sorting by connector name keeps the same layout from one run to the other
+        let mut heads: Vec<HeadInfo> = state
+            .heads
+            .values()
+            .filter(|head| !head.modes.is_empty())
+            .cloned()
+            .collect();
+        heads.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut configuration = Configuration::new();
+        let mut next_x = 0;
+
+        for head in &heads {
+            let mode = head
+                .get_preferred_mode()
+                .or_else(|| head.get_current_or_preferred_mode())
+                .unwrap();
+
+            configuration
+                .enable_head(head)
+                .set_mode(mode)
+                .set_scale(1.0)
+                .set_transform(Transform::Normal)
+                .set_position(next_x, 0);
+
+            next_x += mode.width;
+        }
+
+        let result = apply(state, event_queue, &configuration)?;
+
+        handle_result(
+            result,
+            "Failed to reset displays to their preferred settings",
+        )
+    }
+}
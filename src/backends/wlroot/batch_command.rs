@@ -0,0 +1,79 @@
+use crate::backends::common::{apply, handle_result, remember_head_states};
+use crate::backends::wlroot::connect_trait::WaylandCommand;
+use crate::commands::commands::CommandError;
+use crate::configuration::Configuration;
+use crate::model::AppData;
+use wayland_client::EventQueue;
+
+/// A command of a batch file, kept with its line for the error messages.
+pub struct BatchEntry {
+    pub line_number: usize,
+    pub line: String,
+    pub command: Box<dyn WaylandCommand>,
+    /// Given on the line itself, it only applies to this command.
+    pub force: bool,
+}
+
+/// Runs several commands over a single connection.
+///
+/// When merged, the changes of every command are sent in one configuration once they all ran. The
+/// commands then all see the displays as they were before the batch.
+pub struct BatchCommand {
+    pub entries: Vec<BatchEntry>,
+    pub merge: bool,
+    pub keep_going: bool,
+}
+
+impl WaylandCommand for BatchCommand {
+    fn run(
+        &self,
+        event_queue: &mut EventQueue<AppData>,
+        state: &mut AppData,
+    ) -> Result<(), CommandError> {
+        if self.merge {
+            state.batch_configuration = Some(Configuration::new());
+        }
+
+        let batch_force = state.options.force;
+
+        let mut failed = 0;
+        for entry in &self.entries {
+            state.options.force = batch_force || entry.force;
+            let result = entry.command.run(event_queue, state);
+            state.options.force = batch_force;
+
+            if let Err(error) = result {
+                eprintln!("Line {} \"{}\": {}", entry.line_number, entry.line, error);
+                failed += 1;
+
+                if !self.keep_going {
+                    break;
+                }
+            }
+        }
+
+        if let Some(configuration) = state.batch_configuration.take() {
+            // Without --keep-going, nothing is applied when a command failed
+            if failed == 0 || self.keep_going {
+                // A line forcing its change forces the configuration holding it
+                state.options.force = batch_force || self.entries.iter().any(|entry| entry.force);
+                let saved_states = std::mem::take(&mut state.batch_saved_states);
+                let result = apply(state, event_queue, &configuration)?;
+                handle_result(result, "Failed to apply the changes of the batch")?;
+
+                for (file_name, states) in saved_states {
+                    remember_head_states(state, file_name, states);
+                }
+            }
+        }
+
+        if failed > 0 {
+            return Err(CommandError::BatchFailed {
+                failed,
+                total: self.entries.len(),
+            });
+        }
+
+        Ok(())
+    }
+}
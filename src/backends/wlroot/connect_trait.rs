@@ -1,39 +1,254 @@
+use crate::backends::common::{dispatch_before, dispatch_until, roundtrip};
+use crate::commands::commands::{CommandError, Executable, GlobalOptions};
 use crate::handles::OUTPUT_MANAGER_INTERFACE_NAME;
 use crate::model::AppData;
+use crate::trace;
 use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
 use std::process::exit;
-use wayland_client::{Connection, EventQueue};
+use std::time::{Duration, Instant};
+use wayland_client::{Connection, EventQueue, Proxy};
+
+/// How long the compositor has to acknowledge the stop of the output manager.
+const STOP_TIMEOUT: Duration = Duration::from_secs(1);
 
 pub trait WaylandCommand {
-    fn connect(&self) -> (EventQueue<AppData>, AppData) {
-        let conn = Connection::connect_to_env().expect("failed to connect to a Wayland compositor");
-        let display = conn.display();
-        let mut event_queue = conn.new_event_queue::<AppData>();
-
-        let _registry = display.get_registry(&event_queue.handle(), ());
-
-        let mut state = AppData {
-            initial_done: false,
-            heads: HashMap::new(),
-            manager: None,
-            config_result: None,
-            config_serial: None,
-            output_manager_found: false,
-        };
+    fn connect(
+        &self,
+        options: &GlobalOptions,
+    ) -> Result<(EventQueue<AppData>, AppData), CommandError> {
+        connect_to_output_manager(options)
+    }
 
-        event_queue.roundtrip(&mut state).unwrap();
+    /// Tells the compositor that no more configuration changes are wanted and waits until it is done with
+    /// the output manager, without waiting for long since the process is about to exit anyway.
+    fn disconnect(&self, event_queue: &mut EventQueue<AppData>, state: &mut AppData) {
+        stop_output_manager(event_queue, state);
+    }
 
-        if !state.output_manager_found {
-            eprintln!(
-                "Your system does not support the {} interface. This tool only works on wlroots compositors.",
-                OUTPUT_MANAGER_INTERFACE_NAME
-            );
-            exit(1)
+    /// Runs the command on an established connection, so that several commands can share it.
+    fn run(
+        &self,
+        event_queue: &mut EventQueue<AppData>,
+        state: &mut AppData,
+    ) -> Result<(), CommandError>;
+}
+
+impl<T: WaylandCommand + ?Sized> Executable for T {
+    fn execute(&self, options: &GlobalOptions) {
+        if !options.all_sockets {
+            if let Err(error) = connect_and_run(self, options) {
+                eprintln!("{}", error);
+                exit(error.exit_code())
+            }
+            return;
         }
 
-        while !state.initial_done {
-            event_queue.blocking_dispatch(&mut state).unwrap();
+        let sockets = find_sockets().unwrap_or_else(|error| {
+            eprintln!("{}", error);
+            exit(error.exit_code())
+        });
+
+        // Every compositor is queried even when one fails, the exit code is the one of the first failure
+        let mut exit_code = 0;
+        for (index, socket) in sockets.iter().enumerate() {
+            if index > 0 {
+                println!();
+            }
+            println!("{}:", socket.display());
+
+            let socket_options = GlobalOptions {
+                socket: Some(socket.clone()),
+                all_sockets: false,
+                ..options.clone()
+            };
+            if let Err(error) = connect_and_run(self, &socket_options) {
+                eprintln!("{}", error);
+                if exit_code == 0 {
+                    exit_code = error.exit_code();
+                }
+            }
+        }
+
+        if exit_code != 0 {
+            exit(exit_code)
+        }
+    }
+}
+
+/// Connects to the compositor and waits until it described every display, failing when it has no
+/// output manager.
+pub fn connect_to_output_manager(
+    options: &GlobalOptions,
+) -> Result<(EventQueue<AppData>, AppData), CommandError> {
+    let (mut event_queue, mut state) = open_connection(options)?;
+
+    if !state.output_manager_found {
+        return Err(CommandError::InvalidRequest(format!(
+            "Your system does not support the {} interface. This tool only works on wlroots compositors.",
+            OUTPUT_MANAGER_INTERFACE_NAME
+        )));
+    }
+
+    dispatch_until(&mut state, &mut event_queue, "list the displays", |state| {
+        state.initial_done
+    })?;
+
+    Ok((event_queue, state))
+}
+
+/// Stops the output manager and waits for the compositor to acknowledge it, for `STOP_TIMEOUT` at most.
+pub fn stop_output_manager(event_queue: &mut EventQueue<AppData>, state: &mut AppData) {
+    let Some(manager) = &state.manager else {
+        return;
+    };
+    manager.stop();
+    trace::request(&manager.id(), || String::from("stop()"));
+
+    let deadline = Instant::now() + STOP_TIMEOUT;
+    while state.manager.is_some() {
+        match dispatch_before(state, event_queue, Some(deadline)) {
+            Ok(true) => {}
+            // Timed out or lost the connection, there is nothing left to clean up
+            _ => break,
+        }
+    }
+}
+
+/// Connects to the compositor and receives the globals it advertises, binding the output manager when
+/// there is one.
+pub fn open_connection(
+    options: &GlobalOptions,
+) -> Result<(EventQueue<AppData>, AppData), CommandError> {
+    let conn = match &options.socket {
+        Some(socket) => {
+            let path = socket_path(socket)?;
+            UnixStream::connect(&path)
+                .map(Connection::from_socket)
+                .map_err(|error| {
+                    CommandError::InvalidRequest(format!(
+                        "Unable to connect to the Wayland socket {}: {}",
+                        path.display(),
+                        error
+                    ))
+                })?
         }
-        (event_queue, state)
+        None => Connection::connect_to_env(),
     }
+    .map_err(|error| {
+        CommandError::InvalidRequest(format!(
+            "Unable to connect to a Wayland compositor: {}",
+            error
+        ))
+    })?;
+    let display = conn.display();
+    let mut event_queue = conn.new_event_queue::<AppData>();
+
+    let registry = display.get_registry(&event_queue.handle(), ());
+    trace::request(&display.id(), || {
+        format!("get_registry(new {})", registry.id())
+    });
+
+    let mut state = AppData {
+        connection: conn.clone(),
+        initial_done: false,
+        heads: HashMap::new(),
+        manager: None,
+        config_result: None,
+        config_serial: None,
+        output_manager_found: false,
+        manager_version: 0,
+        globals: vec![],
+        options: options.clone(),
+        batch_configuration: None,
+        batch_saved_states: vec![],
+    };
+
+    roundtrip(&mut state, &mut event_queue)?;
+
+    Ok((event_queue, state))
+}
+
+/// The socket the connection goes to and where it comes from, eg. `/run/user/1000/wayland-1 (WAYLAND_DISPLAY)`.
+pub fn describe_socket(options: &GlobalOptions) -> String {
+    if let Some(socket) = &options.socket {
+        return match socket_path(socket) {
+            Ok(path) => format!("{} (--socket)", path.display()),
+            Err(error) => format!("{} (--socket, {})", socket.display(), error),
+        };
+    }
+    if let Some(fd) = env::var_os("WAYLAND_SOCKET") {
+        return format!("file descriptor {} (WAYLAND_SOCKET)", fd.to_string_lossy());
+    }
+
+    let (display, origin) = match env::var_os("WAYLAND_DISPLAY") {
+        Some(display) => (PathBuf::from(display), "WAYLAND_DISPLAY"),
+        None => (
+            PathBuf::from("wayland-0"),
+            "default, WAYLAND_DISPLAY is not set",
+        ),
+    };
+    match socket_path(&display) {
+        Ok(path) => format!("{} ({})", path.display(), origin),
+        Err(error) => format!("{} ({}, {})", display.display(), origin, error),
+    }
+}
+
+fn connect_and_run<T: WaylandCommand + ?Sized>(
+    command: &T,
+    options: &GlobalOptions,
+) -> Result<(), CommandError> {
+    let (mut event_queue, mut state) = command.connect(options)?;
+
+    let result = command.run(&mut event_queue, &mut state);
+    command.disconnect(&mut event_queue, &mut state);
+    result
+}
+
+fn runtime_dir() -> Result<PathBuf, CommandError> {
+    env::var_os("XDG_RUNTIME_DIR")
+        .map(PathBuf::from)
+        .ok_or_else(|| CommandError::InvalidRequest(String::from("XDG_RUNTIME_DIR is not set")))
+}
+
+/// A socket given by name, eg. `wayland-1`, is looked up in `$XDG_RUNTIME_DIR` like `WAYLAND_DISPLAY`.
+fn socket_path(socket: &Path) -> Result<PathBuf, CommandError> {
+    if socket.is_absolute() || socket.components().count() > 1 {
+        Ok(socket.to_path_buf())
+    } else {
+        Ok(runtime_dir()?.join(socket))
+    }
+}
+
+/// The `wayland-*` sockets in `$XDG_RUNTIME_DIR`, sorted by name.
+fn find_sockets() -> Result<Vec<PathBuf>, CommandError> {
+    let dir = runtime_dir()?;
+    let entries = fs::read_dir(&dir).map_err(|error| {
+        CommandError::InvalidRequest(format!("Unable to read {}: {}", dir.display(), error))
+    })?;
+
+    let mut sockets: Vec<PathBuf> = entries
+        .filter_map(Result::ok)
+        .filter(|entry| {
+            entry.file_name().to_string_lossy().starts_with("wayland-")
+                && entry
+                    .file_type()
+                    .is_ok_and(|file_type| file_type.is_socket())
+        })
+        .map(|entry| entry.path())
+        .collect();
+    sockets.sort();
+
+    if sockets.is_empty() {
+        return Err(CommandError::InvalidRequest(format!(
+            "No Wayland socket found in {}",
+            dir.display()
+        )));
+    }
+
+    Ok(sockets)
 }
@@ -0,0 +1,268 @@
+use crate::backends::common::{dispatch_until, send_configuration};
+use crate::backends::wlroot::connect_trait::{WaylandCommand, describe_socket, open_connection};
+use crate::commands::commands::{CommandError, DoctorCommand, GlobalOptions};
+use crate::configuration::Configuration;
+use crate::handles::{OUTPUT_MANAGER_INTERFACE_NAME, OUTPUT_MANAGER_VERSION};
+use crate::head_printer::{print_globals, print_identification};
+use crate::model::{AppData, ConfigResult, HeadInfo, HeadState};
+use clap::crate_version;
+use wayland_client::EventQueue;
+use wayland_protocols_wlr::output_management::v1::client::zwlr_output_configuration_head_v1::REQ_SET_ADAPTIVE_SYNC_SINCE;
+use wayland_protocols_wlr::output_management::v1::client::zwlr_output_head_v1::EVT_MAKE_SINCE;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Compositor {
+    Hyprland,
+    KWin,
+    Mutter,
+    Weston,
+    Cosmic,
+    River,
+}
+
+impl Compositor {
+    /// Guesses the compositor from the globals only it advertises, since the protocol does not tell.
+    fn from_globals(globals: &[(String, u32)]) -> Option<Compositor> {
+        globals.iter().find_map(|(interface, _)| {
+            let interface = interface.as_str();
+            if interface.starts_with("hyprland_") {
+                Some(Compositor::Hyprland)
+            } else if interface.starts_with("org_kde_kwin_") || interface.starts_with("kde_") {
+                Some(Compositor::KWin)
+            } else if interface == "gtk_shell1" {
+                Some(Compositor::Mutter)
+            } else if interface.starts_with("weston_") {
+                Some(Compositor::Weston)
+            } else if interface.starts_with("zcosmic_") || interface.starts_with("cosmic_") {
+                Some(Compositor::Cosmic)
+            } else if interface.starts_with("river_") || interface.starts_with("zriver_") {
+                Some(Compositor::River)
+            } else {
+                None
+            }
+        })
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Compositor::Hyprland => "Hyprland",
+            Compositor::KWin => "KWin",
+            Compositor::Mutter => "Mutter",
+            Compositor::Weston => "Weston",
+            Compositor::Cosmic => "COSMIC",
+            Compositor::River => "river",
+        }
+    }
+}
+
+impl WaylandCommand for DoctorCommand {
+    /// Unlike the other commands, a compositor without output manager is reported instead of refused.
+    fn connect(
+        &self,
+        options: &GlobalOptions,
+    ) -> Result<(EventQueue<AppData>, AppData), CommandError> {
+        println!("wlout {}", crate_version!());
+        println!("Socket: {}", describe_socket(options));
+
+        let (mut event_queue, mut state) = open_connection(options)?;
+        if state.output_manager_found {
+            dispatch_until(&mut state, &mut event_queue, "list the displays", |state| {
+                state.initial_done
+            })?;
+        }
+
+        Ok((event_queue, state))
+    }
+
+    fn run(
+        &self,
+        event_queue: &mut EventQueue<AppData>,
+        state: &mut AppData,
+    ) -> Result<(), CommandError> {
+        let compositor = Compositor::from_globals(&state.globals);
+        println!(
+            "Compositor: {}",
+            compositor.map_or("unknown", |compositor| compositor.name())
+        );
+
+        println!("\nGlobals:");
+        print_globals(&state.globals);
+
+        println!();
+        if state.output_manager_found {
+            println!(
+                "Output manager: version {} bound, wlout supports up to {}",
+                state.manager_version, OUTPUT_MANAGER_VERSION
+            );
+        } else {
+            println!(
+                "Output manager: {} not advertised",
+                OUTPUT_MANAGER_INTERFACE_NAME
+            );
+        }
+
+        let mut heads: Vec<HeadInfo> = state.heads.values().cloned().collect();
+        heads.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let test_result = match heads.is_empty() {
+            true => None,
+            false => {
+                println!("\nDisplays:");
+                print_identification(&heads);
+
+                // Nothing changes, the compositor is only asked whether it accepts its own current state
+                let result =
+                    send_configuration(state, event_queue, &current_configuration(&heads), true);
+                println!(
+                    "\nNo-op test configuration: {}",
+                    match &result {
+                        Ok(ConfigResult::Succeeded) => String::from("succeeded"),
+                        Ok(ConfigResult::Failed) => String::from("failed"),
+                        Ok(ConfigResult::Cancelled) => String::from("cancelled"),
+                        Err(error) => format!("error, {}", error),
+                    }
+                );
+                Some(result)
+            }
+        };
+
+        let problems = known_problems(state, compositor, &heads, test_result);
+        println!("\nKnown problems:");
+        if problems.is_empty() {
+            println!("None");
+        }
+        for problem in problems {
+            println!("- {}", problem);
+        }
+
+        Ok(())
+    }
+}
+
+/// The heads exactly as the compositor reports them, without the normalization of the other commands
+/// which would move an offset layout.
+fn current_configuration(heads: &[HeadInfo]) -> Configuration {
+    let mut configuration = Configuration::new();
+    for head in heads {
+        configuration.set_head_state(head, &HeadState::from(head));
+        if let Some(mode) = head.get_current_mode().filter(|_| head.enabled) {
+            configuration.enable_head(head).set_mode(mode);
+        }
+    }
+
+    configuration
+}
+
+fn known_problems(
+    state: &AppData,
+    compositor: Option<Compositor>,
+    heads: &[HeadInfo],
+    test_result: Option<Result<ConfigResult, CommandError>>,
+) -> Vec<String> {
+    let mut problems = vec![];
+
+    if !state.output_manager_found {
+        problems.push(match compositor {
+            Some(Compositor::KWin) => format!(
+                "KWin does not implement {}, use kscreen-doctor to configure the displays",
+                OUTPUT_MANAGER_INTERFACE_NAME
+            ),
+            Some(Compositor::Mutter) => format!(
+                "Mutter does not implement {}, use the GNOME settings to configure the displays",
+                OUTPUT_MANAGER_INTERFACE_NAME
+            ),
+            _ => format!(
+                "The compositor does not implement {}, wlout cannot configure its displays",
+                OUTPUT_MANAGER_INTERFACE_NAME
+            ),
+        });
+        return problems;
+    }
+
+    if !state.supports(EVT_MAKE_SINCE) {
+        problems.push(format!(
+            "Displays only report their make, model and serial number since version {} of {}",
+            EVT_MAKE_SINCE, OUTPUT_MANAGER_INTERFACE_NAME
+        ));
+    }
+    if !state.supports(REQ_SET_ADAPTIVE_SYNC_SINCE) {
+        problems.push(format!(
+            "Adaptive sync can only be changed since version {} of {}",
+            REQ_SET_ADAPTIVE_SYNC_SINCE, OUTPUT_MANAGER_INTERFACE_NAME
+        ));
+    }
+    if compositor == Some(Compositor::Hyprland) {
+        problems.push(String::from(
+            "Hyprland applies the monitor settings of its configuration again when it reloads, undoing the changes made by wlout",
+        ));
+    }
+
+    if heads.is_empty() {
+        problems.push(String::from(
+            "The compositor reports no display, eg. a headless session without outputs",
+        ));
+    }
+    for head in heads {
+        if state.supports(EVT_MAKE_SINCE) && head.serial.as_deref().unwrap_or_default().is_empty() {
+            problems.push(format!(
+                "Display {} reports no serial number, it cannot be told apart from an identical display",
+                head.name.as_deref().unwrap_or("N/A")
+            ));
+        }
+    }
+
+    match test_result {
+        Some(Ok(ConfigResult::Failed)) => problems.push(String::from(
+            "The compositor rejects the displays in their current state, every configuration is likely to fail",
+        )),
+        Some(Ok(ConfigResult::Cancelled)) => problems.push(String::from(
+            "The displays changed during the test configuration, run doctor again",
+        )),
+        Some(Err(error)) => problems.push(format!(
+            "The test configuration could not be completed: {}",
+            error
+        )),
+        _ => {}
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::net::UnixStream;
+    use wayland_client::{Connection, Proxy};
+    use wayland_protocols_wlr::output_management::v1::client::zwlr_output_head_v1::ZwlrOutputHeadV1;
+
+    fn head(connection: &Connection, enabled: bool) -> HeadInfo {
+        let mut head = HeadInfo::new(ZwlrOutputHeadV1::inert(connection.backend().downgrade()));
+        head.name = Some(String::from("DP-1"));
+        head.enabled = enabled;
+        head.position_x = Some(1920);
+        head.position_y = Some(1080);
+        head.scale = Some(1.5);
+        head
+    }
+
+    #[test]
+    fn no_op_test_keeps_an_offset_layout() {
+        let connection = Connection::from_socket(UnixStream::pair().unwrap().0).unwrap();
+
+        let configuration = current_configuration(&[head(&connection, true)]);
+
+        let head_configuration = &configuration.heads()[0];
+        assert!(head_configuration.enabled);
+        assert_eq!(head_configuration.position, Some((1920, 1080)));
+        assert_eq!(head_configuration.scale, Some(1.5));
+    }
+
+    #[test]
+    fn no_op_test_keeps_a_display_off() {
+        let connection = Connection::from_socket(UnixStream::pair().unwrap().0).unwrap();
+
+        let configuration = current_configuration(&[head(&connection, false)]);
+
+        assert!(!configuration.heads()[0].enabled);
+    }
+}
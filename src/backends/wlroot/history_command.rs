@@ -0,0 +1,139 @@
+use crate::backends::common::{apply, handle_result};
+use crate::backends::wlroot::connect_trait::WaylandCommand;
+use crate::commands::commands::{
+    CommandError, Executable, GlobalOptions, HistoryCommand, UndoCommand,
+};
+use crate::configuration::Configuration;
+use crate::head_printer::print_history;
+use crate::history::load_history;
+use crate::model::{AppData, HeadInfo, HeadState};
+use wayland_client::EventQueue;
+
+impl Executable for HistoryCommand {
+    fn execute(&self, _options: &GlobalOptions) {
+        let entries = load_history();
+
+        if entries.is_empty() {
+            println!("No change recorded yet");
+            return;
+        }
+
+        print_history(entries)
+    }
+}
+
+impl WaylandCommand for UndoCommand {
+    fn run(
+        &self,
+        event_queue: &mut EventQueue<AppData>,
+        state: &mut AppData,
+    ) -> Result<(), CommandError> {
+        let entries = load_history();
+
+        if self.index > entries.len() {
+            return Err(CommandError::InvalidRequest(format!(
+                "There are only {} changes in the history, see wlout history",
+                entries.len()
+            )));
+        }
+        let entry = &entries[entries.len() - self.index];
+
+        let mut to_restore: Vec<(HeadInfo, &HeadState)> = vec![];
+        for before in &entry.before {
+            let Some(head) = state.find_head_for_state(before) else {
+                eprintln!(
+                    "Warning: display {} is no longer connected and will not be restored",
+                    before.name
+                );
+                continue;
+            };
+
+            let recorded = entry
+                .after
+                .iter()
+                .find(|after| after.is_same_display(before));
+            if changed_since(recorded, &HeadState::from(head)) {
+                eprintln!(
+                    "Warning: display {} has changed since this entry was recorded, these changes will be lost",
+                    before.name
+                );
+            }
+
+            to_restore.push((head.clone(), before));
+        }
+
+        if to_restore.is_empty() {
+            return Err(CommandError::InvalidRequest(format!(
+                "None of the displays changed by \"{}\" is connected",
+                entry.command
+            )));
+        }
+
+        let mut configuration = Configuration::new();
+        for (head, before) in &to_restore {
+            configuration.set_head_state(head, before);
+        }
+
+        let result = apply(state, event_queue, &configuration)?;
+
+        handle_result(result, &format!("Failed to undo \"{}\"", entry.command))
+    }
+}
+
+/// Whether the display no longer is as the entry recorded it. The saved states lack the refresh rate in mHz
+/// and the scale is rounded by the compositor, so the states are compared property by property.
+fn changed_since(recorded: Option<&HeadState>, current: &HeadState) -> bool {
+    recorded.is_none_or(|recorded| !recorded.changes_to(current).is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::HeadModeInput;
+    use wayland_client::protocol::wl_output::Transform;
+
+    fn current() -> HeadState {
+        HeadState {
+            name: String::from("DP-1"),
+            make: Some(String::from("Dell Inc.")),
+            model: Some(String::from("DELL U2415")),
+            serial: Some(String::from("7MT0176J0ABL")),
+            enabled: true,
+            mode: Some(HeadModeInput {
+                width: 1920,
+                height: 1200,
+                rate: 59,
+            }),
+            refresh: Some(59950),
+            position: Some((1920, 0)),
+            scale: Some(1.25),
+            transform: Some(Transform::Normal),
+            adaptive_sync: None,
+        }
+    }
+
+    /// The state as written in the history file and read back by `load_history`.
+    fn reloaded(state: &HeadState) -> HeadState {
+        state.to_string().parse().unwrap()
+    }
+
+    #[test]
+    fn reloaded_state_is_unchanged() {
+        let recorded = reloaded(&current());
+
+        assert_eq!(recorded.refresh, None);
+        assert!(!changed_since(Some(&recorded), &current()));
+    }
+
+    #[test]
+    fn display_changed_after_the_entry() {
+        let recorded = reloaded(&current());
+        let moved = HeadState {
+            position: Some((0, 0)),
+            ..current()
+        };
+
+        assert!(changed_since(Some(&recorded), &moved));
+        assert!(changed_since(None, &current()));
+    }
+}
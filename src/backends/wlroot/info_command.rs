@@ -1,15 +1,31 @@
-use crate::commands::commands::{Executable};
 use crate::backends::wlroot::connect_trait::WaylandCommand;
-use crate::commands::commands::InfoCommand;
-use crate::head_printer::print_heads_detail;
+use crate::commands::commands::{CommandError, InfoCommand};
+use crate::edid::{fill_missing_identity, read_edid};
+use crate::head_printer::{print_edid_detail, print_head_parsable, print_heads_detail};
+use crate::model::AppData;
+use wayland_client::EventQueue;
 
-impl WaylandCommand for InfoCommand {}
+impl WaylandCommand for InfoCommand {
+    fn run(&self, _: &mut EventQueue<AppData>, state: &mut AppData) -> Result<(), CommandError> {
+        let mut target_head = state.get_head(self.name.as_str())?;
 
-impl Executable for InfoCommand {
-    fn execute(&self) {
-        let (_, state) = &self.connect();
-        let target_head = state.get_head(self.name.as_str());
+        let edid = read_edid(&self.sysfs_root, &self.name);
+        if let Some(edid) = &edid {
+            fill_missing_identity(&mut target_head, edid);
+        }
 
-        print_heads_detail(vec![target_head])
+        if self.parsable {
+            print_head_parsable(&target_head);
+            return Ok(());
+        }
+
+        print_heads_detail(vec![target_head]);
+
+        if let Some(edid) = edid {
+            println!();
+            print_edid_detail(vec![(self.name.clone(), edid)]);
+        }
+
+        Ok(())
     }
 }
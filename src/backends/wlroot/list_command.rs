@@ -1,16 +1,21 @@
 use crate::backends::wlroot::connect_trait::WaylandCommand;
-use crate::commands::commands::{Executable, ListCommand};
+use crate::commands::commands::{CommandError, ListCommand};
+use crate::edid::{fill_missing_identity, read_edid};
 use crate::head_printer::print_heads_detail;
-use crate::model::HeadInfo;
-
-impl WaylandCommand for ListCommand {}
-
-impl Executable for ListCommand {
-    fn execute(&self) {
-        let (_, state) = self.connect();
+use crate::model::{AppData, HeadInfo};
+use wayland_client::EventQueue;
 
+impl WaylandCommand for ListCommand {
+    fn run(&self, _: &mut EventQueue<AppData>, state: &mut AppData) -> Result<(), CommandError> {
         if self.verbose {
-            print_heads_detail(state.heads.into_values().collect::<Vec<HeadInfo>>())
+            let mut heads = state.heads.values().cloned().collect::<Vec<HeadInfo>>();
+            for head in heads.iter_mut() {
+                if let Some(edid) = read_edid(&self.sysfs_root, head.name.as_deref().unwrap()) {
+                    fill_missing_identity(head, &edid);
+                }
+            }
+
+            print_heads_detail(heads)
         } else {
             for (i, head) in state.heads.iter().enumerate() {
                 if i == state.heads.iter().len() - 1 {
@@ -20,5 +25,7 @@ impl Executable for ListCommand {
                 }
             }
         }
+
+        Ok(())
     }
 }
@@ -1,21 +1,23 @@
 use crate::backends::common::{apply, handle_result};
 use crate::backends::wlroot::connect_trait::WaylandCommand;
-use crate::commands::commands::{Executable, MirrorCommand};
-use crate::model::{get_best_display_modes, get_common_modes};
-
-impl WaylandCommand for MirrorCommand {}
-
-impl Executable for MirrorCommand {
-    fn execute(&self) {
+use crate::commands::commands::{CommandError, MirrorCommand};
+use crate::configuration::Configuration;
+use crate::model::{AppData, get_best_display_modes, get_common_modes};
+use wayland_client::EventQueue;
+
+impl WaylandCommand for MirrorCommand {
+    fn run(
+        &self,
+        event_queue: &mut EventQueue<AppData>,
+        state: &mut AppData,
+    ) -> Result<(), CommandError> {
         let mirrored_display_name = &self.mirrored_display_name;
         let reference_display_name = &self.reference_display_name;
 
-        let (mut event_queue, mut state) = self.connect();
-
         let (_, moved_display_info, _, reference_display_info) = {
-            let moved_display_info = state.get_head(mirrored_display_name);
+            let moved_display_info = state.get_head(mirrored_display_name)?;
 
-            let reference_display_info = state.get_head(reference_display_name);
+            let reference_display_info = state.get_head(reference_display_name)?;
 
             let moved_display_mode = moved_display_info
                 .get_current_mode()
@@ -35,24 +37,22 @@ impl Executable for MirrorCommand {
         let (modes_1, modes_2) = get_common_modes(&moved_display_info, &reference_display_info);
         let (best_mode_1, best_mode_2) = get_best_display_modes(modes_1, modes_2);
 
-        let result = apply(&mut state, &mut event_queue, |config, qh| {
-            let moved_display_config = config.enable_head(&moved_display_info.head, &qh, ());
-            let ref_display_config = config.enable_head(&reference_display_info.head, &qh, ());
-
-            moved_display_config.set_position(
+        let mut configuration = Configuration::new();
+        configuration
+            .enable_head(&moved_display_info)
+            .set_position(
                 reference_display_info.position_x.unwrap(),
                 reference_display_info.position_y.unwrap(),
-            );
-            moved_display_config.set_mode(&best_mode_1.clone().mode.unwrap());
-            ref_display_config.set_mode(&best_mode_2.clone().mode.unwrap());
-        });
+            )
+            .set_mode(&best_mode_1);
+        configuration
+            .enable_head(&reference_display_info)
+            .set_mode(&best_mode_2);
 
-        let success =
-            format!("Mirrored display {mirrored_display_name}({best_mode_1}) same-as {reference_display_name}({best_mode_2}).
+        let result = apply(state, event_queue, &configuration)?;
 
-Using {best_mode_1} and {best_mode_2} as best common resolution.");
         let fail =
             format!("Unable to mirror display {mirrored_display_name} as {reference_display_name}");
-        handle_result(result, &success, &fail)
+        handle_result(result, &fail)
     }
 }
@@ -1,7 +1,15 @@
-mod connect_trait;
+mod auto_command;
+pub mod batch_command;
+pub mod connect_trait;
+mod doctor_command;
+mod history_command;
 mod info_command;
 mod list_command;
+mod mirror_command;
 mod mode_command;
 mod move_command;
-mod mirror_command;
+mod only_command;
 mod power_command;
+mod probe_command;
+mod scale_command;
+mod wait_command;
@@ -1,155 +1,262 @@
-use crate::backends::common::{apply, handle_result, prompt};
+use crate::backends::common::{apply, confirm, handle_result, test};
 use crate::backends::wlroot::connect_trait::WaylandCommand;
-use crate::commands::commands::Executable;
+use crate::commands::commands::{CommandError, Executable, GlobalOptions};
 use crate::commands::commands::{
-    ModeAutoCommand, ModeCurrentCommand, ModeListCommand, ModePreferredCommand, ModeSetCommand,
+    ModeAutoCommand, ModeCalcCommand, ModeCurrentCommand, ModeListCommand, ModePreferredCommand,
+    ModeSetCommand,
 };
-use crate::model::{AppData, ConfigResult, HeadMode};
-use std::process::exit;
+use crate::configuration::Configuration;
+use crate::head_printer::print_mode_timings;
+use crate::model::{AppData, ConfigResult, HeadInfo, HeadMode, HeadModeInput};
+use crate::timing::{cvt, cvt_reduced_blanking};
+use wayland_client::EventQueue;
 
-impl WaylandCommand for ModeCurrentCommand {}
-impl WaylandCommand for ModeAutoCommand {}
-impl WaylandCommand for ModePreferredCommand {}
-impl WaylandCommand for ModeSetCommand {}
-impl WaylandCommand for ModeListCommand {}
+/// How far from the requested refresh rate custom modes are tested when the compositor rejects one.
+const MAX_CUSTOM_RATE_DELTA: i32 = 10;
+const MAX_SUGGESTIONS: usize = 3;
 
-impl Executable for ModeCurrentCommand {
-    fn execute(&self) {
-        let (_, state) = self.connect();
-        let target_head = state.get_head(self.name.as_str());
+impl WaylandCommand for ModeCurrentCommand {
+    fn run(&self, _: &mut EventQueue<AppData>, state: &mut AppData) -> Result<(), CommandError> {
+        let target_head = state.get_head(self.name.as_str())?;
 
-        let mode = target_head.get_current_mode().expect(&*format!(
-            "No current mode not found on display {}. It is probably off",
-            self.name
-        ));
+        let mode = target_head.get_current_mode().ok_or_else(|| {
+            CommandError::InvalidRequest(format!(
+                "Display {} has no current mode, it is probably off",
+                self.name
+            ))
+        })?;
 
         let string_result = format!("{}x{}@{:.0}", mode.width, mode.height, mode.rate);
-        println!("{}", string_result)
+        println!("{}", string_result);
+
+        Ok(())
     }
 }
 
-impl Executable for ModeAutoCommand {
-    fn execute(&self) {
-        let (mut event_queue, mut state) = self.connect();
-        let target_head = state.get_head(self.name.as_str());
+impl WaylandCommand for ModeAutoCommand {
+    fn run(
+        &self,
+        event_queue: &mut EventQueue<AppData>,
+        state: &mut AppData,
+    ) -> Result<(), CommandError> {
+        let target_head = state.get_head(self.name.as_str())?;
 
-        let mode = get_preferred_mode(self.name.as_str(), &state);
+        let mode = get_preferred_mode(self.name.as_str(), state)?;
 
-        let result = apply(&mut state, &mut event_queue, |config, qh| {
-            let head_config = config.enable_head(&target_head.head, qh, ());
-            head_config.set_mode(&mode.mode.clone().unwrap());
-        });
+        let mut configuration = Configuration::new();
+        configuration.enable_head(&target_head).set_mode(&mode);
+
+        let result = apply(state, event_queue, &configuration)?;
 
-        let success_message = &format!("Auto set mode {} for display {}", mode, self.name);
         let failure_message = &format!("Failed to set mode {} for display {}", mode, self.name);
 
-        handle_result(result, success_message, failure_message);
+        handle_result(result, failure_message)
     }
 }
 
-impl Executable for ModePreferredCommand {
-    fn execute(&self) {
-        let (_, state) = self.connect();
-        let mode = get_preferred_mode(self.name.as_str(), &state);
+impl WaylandCommand for ModePreferredCommand {
+    fn run(&self, _: &mut EventQueue<AppData>, state: &mut AppData) -> Result<(), CommandError> {
+        let mode = get_preferred_mode(self.name.as_str(), state)?;
 
         let string_result = format!("{}x{}@{:.0}", mode.width, mode.height, mode.rate);
-        println!("{}", string_result)
+        println!("{}", string_result);
+
+        Ok(())
     }
 }
 
-impl Executable for ModeSetCommand {
-    fn execute(&self) {
-        let (mut event_queue, mut state) = self.connect();
-
-        let target_head = state.get_head(self.name.as_str());
+impl WaylandCommand for ModeSetCommand {
+    fn run(
+        &self,
+        event_queue: &mut EventQueue<AppData>,
+        state: &mut AppData,
+    ) -> Result<(), CommandError> {
+        let target_head = state.get_head(self.name.as_str())?;
 
         let target_mode = target_head.find_mode(self.mode.width, self.mode.height, self.mode.rate);
 
-        let result: ConfigResult;
+        let mut configuration = Configuration::new();
 
-        if target_mode.is_none() {
-            if !self.force {
-                let prompt_msg = format!(
-                    "The specified mode {} does not exist for display {}. Set it as custom mode for this display ?",
-                    self.mode, self.name
-                );
-                let read = prompt(&prompt_msg);
+        match target_mode {
+            None => {
+                if !state.options.force {
+                    let nearest_modes: Vec<String> = target_head
+                        .nearest_modes(&self.mode, MAX_SUGGESTIONS)
+                        .iter()
+                        .map(|mode| mode.to_string())
+                        .collect();
+                    let timing =
+                        cvt_reduced_blanking(self.mode.width, self.mode.height, self.mode.rate);
+                    let prompt_msg = format!(
+                        "The specified mode {} does not exist for display {} (pixel clock of {:.2} MHz with CVT reduced blanking). Nearest advertised modes: {}. Set it as custom mode for this display ?",
+                        self.mode,
+                        self.name,
+                        timing.pixel_clock_mhz(),
+                        nearest_modes.join(", ")
+                    );
+                    confirm(
+                        &prompt_msg,
+                        &format!(
+                            "Mode {} is not advertised by display {}, the nearest ones are {}",
+                            self.mode,
+                            self.name,
+                            nearest_modes.join(", ")
+                        ),
+                    )?;
+                }
+
+                if test_custom_mode(state, event_queue, &target_head, &self.mode)?
+                    != ConfigResult::Succeeded
+                {
+                    let mut message = format!(
+                        "The compositor rejected the custom mode {} for display {}",
+                        self.mode, self.name
+                    );
 
-                if read.to_lowercase() != "y" {
-                    exit(1)
+                    let alternatives =
+                        find_mode_alternatives(state, event_queue, &target_head, &self.mode)?;
+                    if !alternatives.is_empty() {
+                        let alternatives: Vec<String> =
+                            alternatives.iter().map(|mode| mode.to_string()).collect();
+                        message += &format!(
+                            "\nNearby modes accepted by the compositor: {}",
+                            alternatives.join(", ")
+                        );
+                    }
+                    return Err(CommandError::ConfigurationFailed(message));
                 }
+
+                configuration
+                    .enable_head(&target_head)
+                    .set_custom_mode(&self.mode);
+            }
+            Some(target_mode) => {
+                configuration
+                    .enable_head(&target_head)
+                    .set_mode(target_mode);
             }
-            result = apply(&mut state, &mut event_queue, |config, qh| {
-                let head_config = config.enable_head(&target_head.head, qh, ());
-                head_config.set_custom_mode(self.mode.width, self.mode.height, self.mode.rate);
-            });
-        } else {
-            result = apply(&mut state, &mut event_queue, |config, qh| {
-                let head_config = config.enable_head(&target_head.head, qh, ());
-                head_config.set_mode(&target_mode.unwrap().mode.clone().unwrap());
-            });
         }
-        let success_message = &format!("Set mode {} for display {}", self.mode, self.name);
+
+        let result = apply(state, event_queue, &configuration)?;
         let failure_message =
             &format!("Failed to set mode {} for display {}", self.mode, self.name);
 
-        handle_result(result, success_message, failure_message);
+        handle_result(result, failure_message)
     }
 }
 
-impl Executable for ModeListCommand {
-    fn execute(&self) {
-        let (_, state) = self.connect();
-        for head in state.heads.values() {
-            if head.name == Some(self.name.parse().unwrap()) {
-                let mut modes: Vec<_> = head.modes.values().collect();
-                modes.sort_by(|a, b| {
-                    b.height
-                        .cmp(&a.height)
-                        .then(b.width.cmp(&a.width))
-                        .then(b.rate.cmp(&a.rate))
-                });
-
-                for (i, mode) in modes.iter().enumerate() {
-                    let mut string_result =
-                        format!("{}x{}@{:.0}", mode.width, mode.height, mode.rate);
-
-                    if mode.is_current || mode.is_preferred {
-                        string_result += "(";
-                        if mode.is_preferred {
-                            string_result += "preferred"
-                        }
-                        if mode.is_current {
-                            if mode.is_preferred {
-                                string_result += ","
-                            }
-                            string_result += "current"
-                        }
-                        string_result += ")"
-                    }
+impl WaylandCommand for ModeListCommand {
+    fn run(&self, _: &mut EventQueue<AppData>, state: &mut AppData) -> Result<(), CommandError> {
+        let head = state.get_head(&self.name)?;
+
+        let mut modes: Vec<_> = head.modes.values().collect();
+        modes.sort_by(|a, b| {
+            b.height
+                .cmp(&a.height)
+                .then(b.width.cmp(&a.width))
+                .then(b.rate.cmp(&a.rate))
+        });
 
-                    if i == modes.len() - 1 {
-                        string_result += "\n"
-                    } else {
-                        string_result += "\t"
+        for (i, mode) in modes.iter().enumerate() {
+            let mut string_result = format!("{}x{}@{:.0}", mode.width, mode.height, mode.rate);
+
+            if mode.is_current || mode.is_preferred {
+                string_result += "(";
+                if mode.is_preferred {
+                    string_result += "preferred"
+                }
+                if mode.is_current {
+                    if mode.is_preferred {
+                        string_result += ","
                     }
-                    print!("{}", string_result)
+                    string_result += "current"
                 }
+                string_result += ")"
+            }
+
+            if i == modes.len() - 1 {
+                string_result += "\n"
+            } else {
+                string_result += "\t"
             }
+            print!("{}", string_result)
         }
+
+        Ok(())
     }
 }
 
-fn get_preferred_mode(name: &str, state: &AppData) -> HeadMode {
-    let target_head = state.get_head(name);
+impl Executable for ModeCalcCommand {
+    fn execute(&self, _options: &GlobalOptions) {
+        print_mode_timings(vec![
+            cvt(self.mode.width, self.mode.height, self.mode.rate),
+            cvt_reduced_blanking(self.mode.width, self.mode.height, self.mode.rate),
+        ])
+    }
+}
 
-    let mode = target_head
+fn test_custom_mode(
+    state: &mut AppData,
+    event_queue: &mut EventQueue<AppData>,
+    head: &HeadInfo,
+    mode: &HeadModeInput,
+) -> Result<ConfigResult, CommandError> {
+    let mut configuration = Configuration::new();
+    configuration.enable_head(head).set_custom_mode(mode);
+
+    test(state, event_queue, &configuration)
+}
+
+/// Advertised modes with the same resolution, closest refresh rate first, followed by custom modes
+/// with a nearby refresh rate that pass the compositor test.
+fn find_mode_alternatives(
+    state: &mut AppData,
+    event_queue: &mut EventQueue<AppData>,
+    head: &HeadInfo,
+    mode: &HeadModeInput,
+) -> Result<Vec<HeadModeInput>, CommandError> {
+    let mut advertised: Vec<HeadModeInput> = head
         .modes
         .values()
-        .find(|m| m.is_preferred)
-        .expect(&*format!(
-            "No preferred mode not found on display {}.",
-            name
-        ));
-    mode.clone()
+        .filter(|m| m.width == mode.width && m.height == mode.height)
+        .map(|m| HeadModeInput {
+            width: m.width,
+            height: m.height,
+            rate: m.rate,
+        })
+        .collect();
+    advertised.sort_by_key(|m| (m.rate - mode.rate).abs());
+    advertised.truncate(MAX_SUGGESTIONS);
+
+    let mut accepted = vec![];
+    for delta in 1..=MAX_CUSTOM_RATE_DELTA {
+        for rate in [mode.rate - delta, mode.rate + delta] {
+            let candidate = HeadModeInput {
+                rate,
+                ..mode.clone()
+            };
+
+            if rate <= 0 || accepted.len() >= MAX_SUGGESTIONS || advertised.contains(&candidate) {
+                continue;
+            }
+
+            if test_custom_mode(state, event_queue, head, &candidate)? == ConfigResult::Succeeded {
+                accepted.push(candidate);
+            }
+        }
+    }
+
+    Ok(advertised.into_iter().chain(accepted).collect())
+}
+
+fn get_preferred_mode(name: &str, state: &AppData) -> Result<HeadMode, CommandError> {
+    let target_head = state.get_head(name)?;
+
+    let mode = target_head.get_preferred_mode().ok_or_else(|| {
+        CommandError::InvalidRequest(format!(
+            "Display {} does not advertise a preferred mode, see mode list {} for the available ones",
+            name, name
+        ))
+    })?;
+    Ok(mode.clone())
 }
@@ -1,18 +1,20 @@
 use crate::backends::common::{apply, handle_result};
 use crate::backends::wlroot::connect_trait::WaylandCommand;
-use crate::commands::commands::Executable;
+use crate::commands::commands::CommandError;
 use crate::commands::commands::{
     MoveCommand, MoveRelativeCommand, REL_POS_ABOVE, REL_POS_BELOW, REL_POS_LEFT_OF,
     REL_POS_RIGHT_OF,
 };
+use crate::configuration::Configuration;
+use crate::model::AppData;
+use wayland_client::EventQueue;
 
-impl WaylandCommand for MoveRelativeCommand {}
-impl WaylandCommand for MoveCommand {}
-
-impl Executable for MoveRelativeCommand {
-    fn execute(&self) {
-        let (mut event_queue, mut state) = self.connect();
-
+impl WaylandCommand for MoveRelativeCommand {
+    fn run(
+        &self,
+        event_queue: &mut EventQueue<AppData>,
+        state: &mut AppData,
+    ) -> Result<(), CommandError> {
         let moved_display_name = &self.moved_display_name;
         let reference_display_name = &self.reference_display_name;
 
@@ -22,8 +24,8 @@ impl Executable for MoveRelativeCommand {
             reference_display_mode,
             reference_display_info,
         ) = {
-            let moved_display_info = state.get_head(moved_display_name);
-            let reference_display_info = state.get_head(reference_display_name);
+            let moved_display_info = state.get_head(moved_display_name)?;
+            let reference_display_info = state.get_head(reference_display_name)?;
 
             let moved_display_mode = moved_display_info
                 .get_current_mode()
@@ -40,66 +42,64 @@ impl Executable for MoveRelativeCommand {
             )
         };
 
-        let result = apply(&mut state, &mut event_queue, |config, qh| {
-            let moved_display_config = config.enable_head(&moved_display_info.head, &qh, ());
+        let mut configuration = Configuration::new();
+        let moved_display_config = configuration.enable_head(&moved_display_info);
 
-            match self.pos.as_str() {
-                REL_POS_ABOVE => {
-                    moved_display_config.set_position(
-                        reference_display_info.position_x.unwrap(),
-                        reference_display_info.position_y.unwrap() - moved_display_mode.height,
-                    );
-                }
-                REL_POS_BELOW => {
-                    moved_display_config.set_position(
-                        reference_display_info.position_x.unwrap(),
-                        reference_display_info.position_y.unwrap() + reference_display_mode.height,
-                    );
-                }
-                REL_POS_RIGHT_OF => {
-                    moved_display_config.set_position(
-                        reference_display_info.position_x.unwrap() + reference_display_mode.width,
-                        reference_display_info.position_y.unwrap(),
-                    );
-                }
-                REL_POS_LEFT_OF => {
-                    moved_display_config.set_position(
-                        reference_display_info.position_x.unwrap() - moved_display_mode.width,
-                        reference_display_info.position_y.unwrap(),
-                    );
-                }
-                &_ => todo!(),
+        match self.pos.as_str() {
+            REL_POS_ABOVE => {
+                moved_display_config.set_position(
+                    reference_display_info.position_x.unwrap(),
+                    reference_display_info.position_y.unwrap() - moved_display_mode.height,
+                );
+            }
+            REL_POS_BELOW => {
+                moved_display_config.set_position(
+                    reference_display_info.position_x.unwrap(),
+                    reference_display_info.position_y.unwrap() + reference_display_mode.height,
+                );
+            }
+            REL_POS_RIGHT_OF => {
+                moved_display_config.set_position(
+                    reference_display_info.position_x.unwrap() + reference_display_mode.width,
+                    reference_display_info.position_y.unwrap(),
+                );
+            }
+            REL_POS_LEFT_OF => {
+                moved_display_config.set_position(
+                    reference_display_info.position_x.unwrap() - moved_display_mode.width,
+                    reference_display_info.position_y.unwrap(),
+                );
             }
-        });
+            &_ => todo!(),
+        }
+
+        let result = apply(state, event_queue, &configuration)?;
 
-        let success = format!(
-            "Moved display {moved_display_name} {} {reference_display_name}",
-            self.pos
-        );
         let fail = format!(
             "Unable to move display {moved_display_name} {} {reference_display_name}",
             self.pos
         );
-        handle_result(result, &success, &fail)
+        handle_result(result, &fail)
     }
 }
 
-impl Executable for MoveCommand {
-    fn execute(&self) {
-        let (mut event_queue, mut state) = self.connect();
-        let target_head = state.get_head(self.name.as_str()).head.clone();
+impl WaylandCommand for MoveCommand {
+    fn run(
+        &self,
+        event_queue: &mut EventQueue<AppData>,
+        state: &mut AppData,
+    ) -> Result<(), CommandError> {
+        let target_head = state.get_head(self.name.as_str())?;
 
-        let config_result = apply(&mut state, &mut event_queue, |configuration, qh| {
-            let head_config = configuration.enable_head(&target_head, &qh, ());
-            head_config.set_position(self.x, self.y);
-        });
+        let mut configuration = Configuration::new();
+        configuration
+            .enable_head(&target_head)
+            .set_position(self.x, self.y);
+
+        let config_result = apply(state, event_queue, &configuration)?;
 
-        let success_message = &format!(
-            "Set position for display {} to x: {} y: {}",
-            self.name, self.x, self.y
-        );
         let error_message = &format!("Failed to set position for display {}", self.name);
 
-        handle_result(config_result, success_message, error_message);
+        handle_result(config_result, error_message)
     }
 }
@@ -0,0 +1,131 @@
+use crate::backends::common::{apply, handle_result, remember_head_states};
+use crate::backends::wlroot::connect_trait::WaylandCommand;
+use crate::commands::commands::{AllCommand, CommandError, OnlyCommand};
+use crate::configuration::Configuration;
+use crate::model::{AppData, ConfigResult, HeadInfo, HeadState, logical_size};
+use crate::store::{ONLY_STATE_FILE, load_head_states, remove_head_states};
+use wayland_client::EventQueue;
+
+impl WaylandCommand for OnlyCommand {
+    fn run(
+        &self,
+        event_queue: &mut EventQueue<AppData>,
+        state: &mut AppData,
+    ) -> Result<(), CommandError> {
+        let target_head = state.get_head(&self.name)?;
+        let mode = target_head
+            .get_current_or_preferred_mode()
+            .ok_or_else(|| {
+                CommandError::InvalidRequest(format!(
+                    "Display {} does not advertise any mode",
+                    self.name
+                ))
+            })?
+            .clone();
+
+        let other_heads: Vec<HeadInfo> = state
+            .heads
+            .values()
+            .filter(|head| head.head != target_head.head)
+            .cloned()
+            .collect();
+        let previously_enabled: Vec<HeadState> = state
+            .heads
+            .values()
+            .filter(|head| head.enabled)
+            .map(HeadState::from)
+            .collect();
+
+        let mut configuration = Configuration::new();
+        configuration
+            .enable_head(&target_head)
+            .set_mode(&mode)
+            .set_position(0, 0);
+        for head in &other_heads {
+            configuration.disable_head(head);
+        }
+
+        let result = apply(state, event_queue, &configuration)?;
+
+        // Running `only` twice in a row must not forget the displays turned off the first time
+        let turned_off_others = other_heads.iter().any(|head| head.enabled);
+        if result == ConfigResult::Succeeded && turned_off_others {
+            remember_head_states(state, ONLY_STATE_FILE, previously_enabled);
+        }
+
+        handle_result(
+            result,
+            &format!("Failed to keep only display {} on", self.name),
+        )
+    }
+}
+
+impl WaylandCommand for AllCommand {
+    fn run(
+        &self,
+        event_queue: &mut EventQueue<AppData>,
+        state: &mut AppData,
+    ) -> Result<(), CommandError> {
+        let restored: Vec<(HeadInfo, HeadState)> = load_head_states(ONLY_STATE_FILE)
+            .into_iter()
+            .filter_map(|saved| {
+                state
+                    .find_head_for_state(&saved)
+                    .map(|head| (head.clone(), saved))
+            })
+            .collect();
+
+        let is_restored =
+            |head: &HeadInfo| restored.iter().any(|(other, _)| other.head == head.head);
+
+        let disabled_heads: Vec<HeadInfo> = state
+            .heads
+            .values()
+            .filter(|head| !head.enabled && !is_restored(head))
+            .cloned()
+            .collect();
+
+        if restored.is_empty() && disabled_heads.is_empty() {
+            println!("All displays are already enabled");
+            return Ok(());
+        }
+
+        // Displays that were never seen on are placed to the right of everything else
+        let mut next_x = state
+            .heads
+            .values()
+            .filter(|head| head.enabled && !is_restored(head))
+            .filter_map(|head| Some(head.position_x? + head.logical_size()?.0))
+            .chain(
+                restored
+                    .iter()
+                    .filter_map(|(_, saved)| Some(saved.position?.0 + saved.logical_size()?.0)),
+            )
+            .max()
+            .unwrap_or(0);
+
+        let mut configuration = Configuration::new();
+        for (head, saved) in &restored {
+            configuration.set_head_state(head, saved);
+        }
+
+        for head in &disabled_heads {
+            let head_config = configuration.enable_head(head);
+            head_config.set_position(next_x, 0);
+            if let Some(mode) = head.get_current_or_preferred_mode() {
+                head_config.set_mode(mode);
+                next_x += logical_size(mode.width, mode.height, head.scale, head.transform).0;
+            }
+        }
+
+        let result = apply(state, event_queue, &configuration)?;
+
+        if result == ConfigResult::Succeeded
+            && let Err(error) = remove_head_states(ONLY_STATE_FILE)
+        {
+            eprintln!("Unable to clear the remembered displays: {}", error);
+        }
+
+        handle_result(result, "Failed to enable all displays")
+    }
+}
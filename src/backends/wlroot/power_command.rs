@@ -1,40 +1,93 @@
-use crate::backends::common::{apply, handle_result, prompt};
+use crate::backends::common::{apply, handle_result};
 use crate::backends::wlroot::connect_trait::WaylandCommand;
-use crate::commands::commands::{Executable, PowerCommand};
-use std::process::exit;
-
-impl WaylandCommand for PowerCommand {}
-
-impl Executable for PowerCommand {
-    fn execute(&self) {
-        let (mut event_queue, mut state) = self.connect();
-
-        let target_head = state.get_head(&self.name);
-        let count = state.heads.iter().filter(|(_, head)| head.enabled).count();
-
-        let result = apply(&mut state, &mut event_queue, |config, qh| {
-            if self.on {
-                config.enable_head(&target_head.head, &qh, ());
-            } else {
-                // off
-                if count < 2 && !self.force {
-                    let read =
-                        prompt("You are about to power off your last display.\nProceed ? (Y/n)");
-
-                    if read.to_lowercase() != "y" {
-                        exit(1)
-                    }
-                }
-                config.disable_head(&target_head.head);
-            }
+use crate::commands::commands::{CommandError, PowerCommand};
+use crate::configuration::Configuration;
+use crate::model::{AppData, ConfigResult, HeadInfo, HeadState};
+use crate::store::{POWER_STATE_FILE, load_head_states, remove_head_state, save_head_state};
+use wayland_client::EventQueue;
+
+impl PowerCommand {
+    /// The settings saved when the display was turned off, with the ones given on the command line on top.
+    fn state_to_restore(&self, state: &AppData, target_head: &HeadInfo) -> HeadState {
+        let saved = load_head_states(POWER_STATE_FILE)
+            .into_iter()
+            .find(|saved| {
+                state
+                    .find_head_for_state(saved)
+                    .is_some_and(|head| head.head == target_head.head)
+            });
+
+        let mut restored = saved.unwrap_or_else(|| HeadState {
+            mode: None,
+            refresh: None,
+            position: None,
+            scale: None,
+            transform: None,
+            adaptive_sync: None,
+            ..HeadState::from(target_head)
         });
 
-        let action = if self.on { "enabled" } else { "disabled" };
+        restored.enabled = true;
+        if let Some(mode) = &self.mode {
+            restored.mode = Some(mode.clone());
+            restored.refresh = None;
+        }
+        if let Some(position) = self.position {
+            restored.position = Some(position);
+        }
+        if let Some(scale) = self.scale {
+            restored.scale = Some(scale);
+        }
+        if let Some(transform) = self.transform {
+            restored.transform = Some(transform);
+        }
+        if let Some(adaptive_sync) = self.adaptive_sync {
+            restored.adaptive_sync = Some(adaptive_sync);
+        }
+
+        restored
+    }
+}
+
+impl WaylandCommand for PowerCommand {
+    fn run(
+        &self,
+        event_queue: &mut EventQueue<AppData>,
+        state: &mut AppData,
+    ) -> Result<(), CommandError> {
+        let target_head = state.get_head(&self.name)?;
+
+        if self.on {
+            let restored = self.state_to_restore(state, &target_head);
+
+            let mut configuration = Configuration::new();
+            configuration.set_head_state(&target_head, &restored);
+
+            let result = apply(state, event_queue, &configuration)?;
+
+            if result == ConfigResult::Succeeded
+                && let Err(error) = remove_head_state(POWER_STATE_FILE, &restored)
+            {
+                eprintln!("Unable to clear the saved display settings: {}", error);
+            }
+
+            handle_result(result, &format!("Failed to enable display {}", self.name))
+        } else {
+            let mut configuration = Configuration::new();
+            configuration.disable_head(&target_head);
+
+            let result = apply(state, event_queue, &configuration)?;
+
+            // Turning off a display that is already off must not forget its settings
+            if result == ConfigResult::Succeeded
+                && target_head.enabled
+                && let Err(error) =
+                    save_head_state(POWER_STATE_FILE, &HeadState::from(&target_head))
+            {
+                eprintln!("Unable to save the display settings: {}", error);
+            }
 
-        handle_result(
-            result,
-            &format!("Successfully {} display {}", action, self.name),
-            &format!("Failed to {} display {}", action, self.name),
-        )
+            handle_result(result, &format!("Failed to disable display {}", self.name))
+        }
     }
 }
@@ -0,0 +1,143 @@
+use crate::backends::common::test;
+use crate::backends::wlroot::connect_trait::WaylandCommand;
+use crate::backends::wlroot::scale_command::{MAX_SCALE, MIN_SCALE};
+use crate::commands::commands::{CommandError, ProbeCommand};
+use crate::configuration::{Configuration, HeadConfiguration};
+use crate::head_printer::print_capabilities;
+use crate::model::{
+    ADAPTIVE_SYNC_STATES, AppData, ConfigResult, HeadInfo, HeadModeInput, TRANSFORMS,
+    parse_adaptive_sync,
+};
+use std::fmt::Display;
+use wayland_client::EventQueue;
+use wayland_protocols_wlr::output_management::v1::client::zwlr_output_configuration_head_v1::REQ_SET_ADAPTIVE_SYNC_SINCE;
+
+/// How far above the advertised maximum refresh rate is tested when no maximum is given.
+const DEFAULT_OVERCLOCK_RANGE: i32 = 60;
+
+impl WaylandCommand for ProbeCommand {
+    fn run(
+        &self,
+        event_queue: &mut EventQueue<AppData>,
+        state: &mut AppData,
+    ) -> Result<(), CommandError> {
+        let head = state.get_head(&self.name)?;
+        let mut capabilities = vec![];
+
+        let steps = ((MAX_SCALE - MIN_SCALE) / self.scale_step).floor() as i32;
+        let scales: Vec<f64> = (0..=steps)
+            .map(|i| MIN_SCALE + i as f64 * self.scale_step)
+            .collect();
+        capabilities.push(probe(
+            state,
+            event_queue,
+            &head,
+            "Scale",
+            &scales,
+            |config, scale| {
+                config.set_scale(*scale);
+            },
+        )?);
+
+        let transforms: Vec<&str> = TRANSFORMS.iter().map(|(name, _)| *name).collect();
+        capabilities.push(probe(
+            state,
+            event_queue,
+            &head,
+            "Transform",
+            &transforms,
+            |config, name| {
+                let (_, transform) = TRANSFORMS.iter().find(|(other, _)| other == name).unwrap();
+                config.set_transform(*transform);
+            },
+        )?);
+
+        if state.supports(REQ_SET_ADAPTIVE_SYNC_SINCE) {
+            capabilities.push(probe(
+                state,
+                event_queue,
+                &head,
+                "Adaptive sync",
+                &ADAPTIVE_SYNC_STATES.map(|(name, _)| name),
+                |config, value| {
+                    config.set_adaptive_sync(parse_adaptive_sync(value).unwrap());
+                },
+            )?);
+        } else {
+            capabilities.push((
+                String::from("Adaptive sync"),
+                vec![],
+                vec![String::from(
+                    "unavailable, the compositor protocol is too old",
+                )],
+            ));
+        }
+
+        if let Some(mode) = head.get_current_or_preferred_mode() {
+            let advertised_max = head
+                .modes
+                .values()
+                .filter(|m| m.width == mode.width && m.height == mode.height)
+                .map(|m| m.rate)
+                .max()
+                .unwrap_or(mode.rate);
+            let max_rate = self
+                .max_rate
+                .unwrap_or(advertised_max + DEFAULT_OVERCLOCK_RANGE);
+
+            let modes: Vec<HeadModeInput> = (1..)
+                .map(|i| advertised_max + i * self.rate_step)
+                .take_while(|rate| *rate <= max_rate)
+                .map(|rate| HeadModeInput {
+                    width: mode.width,
+                    height: mode.height,
+                    rate,
+                })
+                .collect();
+            capabilities.push(probe(
+                state,
+                event_queue,
+                &head,
+                &format!(
+                    "Custom mode above {}x{}@{}",
+                    mode.width, mode.height, advertised_max
+                ),
+                &modes,
+                |config, mode| {
+                    config.set_custom_mode(mode);
+                },
+            )?);
+        }
+
+        print_capabilities(capabilities);
+
+        Ok(())
+    }
+}
+
+/// Tests every value on its own, returns the capability name with the accepted and rejected values.
+fn probe<T: Display>(
+    state: &mut AppData,
+    event_queue: &mut EventQueue<AppData>,
+    head: &HeadInfo,
+    capability: &str,
+    values: &[T],
+    configure: impl Fn(&mut HeadConfiguration, &T),
+) -> Result<(String, Vec<String>, Vec<String>), CommandError> {
+    let mut accepted = vec![];
+    let mut rejected = vec![];
+
+    for value in values {
+        let mut configuration = Configuration::new();
+        configure(configuration.enable_head(head), value);
+
+        match test(state, event_queue, &configuration)? {
+            ConfigResult::Succeeded => accepted.push(value.to_string()),
+            ConfigResult::Failed => rejected.push(value.to_string()),
+            // The displays changed in the meantime, the next tests would be cancelled as well
+            ConfigResult::Cancelled => return Err(CommandError::ConfigurationCancelled),
+        }
+    }
+
+    Ok((capability.to_string(), accepted, rejected))
+}
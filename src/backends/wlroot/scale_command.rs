@@ -0,0 +1,195 @@
+use crate::backends::common::{apply, handle_result, test};
+use crate::backends::wlroot::connect_trait::WaylandCommand;
+use crate::commands::commands::{CommandError, ScaleAutoCommand, ScaleEqualizeCommand};
+use crate::configuration::Configuration;
+use crate::layout::{Rect, rearrange_after_resize};
+use crate::model::{AppData, ConfigResult, HeadInfo, logical_size};
+use wayland_client::EventQueue;
+
+pub const MIN_SCALE: f64 = 0.5;
+pub const MAX_SCALE: f64 = 4.0;
+/// How many steps away from the ideal scale are tested when the compositor rejects it.
+const MAX_STEPS_AWAY: i32 = 4;
+
+impl WaylandCommand for ScaleAutoCommand {
+    fn run(
+        &self,
+        event_queue: &mut EventQueue<AppData>,
+        state: &mut AppData,
+    ) -> Result<(), CommandError> {
+        let head = state.get_head(&self.name)?;
+
+        let ppi = get_ppi(&head)?;
+        let scale = pick_scale(state, event_queue, &head, ppi / self.target_dpi, self.step)?;
+
+        let result = apply_scales(state, event_queue, &[(head, scale)])?;
+
+        handle_result(
+            result,
+            &format!("Failed to set scale {} for display {}", scale, self.name),
+        )
+    }
+}
+
+impl WaylandCommand for ScaleEqualizeCommand {
+    fn run(
+        &self,
+        event_queue: &mut EventQueue<AppData>,
+        state: &mut AppData,
+    ) -> Result<(), CommandError> {
+        let heads: Vec<(HeadInfo, f64)> = self
+            .names
+            .iter()
+            .map(|name| {
+                let head = state.get_head(name)?;
+                let ppi = get_ppi(&head)?;
+                Ok((head, ppi))
+            })
+            .collect::<Result<_, CommandError>>()?;
+
+        // Without a target, the least dense display keeps a scale of 1 and the others catch up with it
+        let target_dpi = self.target_dpi.unwrap_or_else(|| {
+            heads
+                .iter()
+                .map(|(_, ppi)| *ppi)
+                .fold(f64::INFINITY, f64::min)
+        });
+
+        let mut scales = vec![];
+        for (head, ppi) in heads {
+            let scale = pick_scale(state, event_queue, &head, ppi / target_dpi, self.step)?;
+            scales.push((head, scale));
+        }
+
+        let result = apply_scales(state, event_queue, &scales)?;
+
+        handle_result(
+            result,
+            &format!("Failed to equalize displays {}", self.names.join(", ")),
+        )
+    }
+}
+
+fn get_ppi(head: &HeadInfo) -> Result<f64, CommandError> {
+    let name = head.name.clone().unwrap();
+
+    if !head.enabled {
+        return Err(CommandError::InvalidRequest(format!(
+            "The display {} is off, turn it on before changing its scale",
+            name
+        )));
+    }
+
+    head.ppi().ok_or_else(|| {
+        CommandError::InvalidRequest(format!(
+            "The display {} does not advertise its physical size, unable to compute its density",
+            name
+        ))
+    })
+}
+
+/// Rounds the ideal scale to the step and asks the compositor to test it, trying the neighbouring steps
+/// when it is refused.
+fn pick_scale(
+    state: &mut AppData,
+    event_queue: &mut EventQueue<AppData>,
+    head: &HeadInfo,
+    ideal: f64,
+    step: f64,
+) -> Result<f64, CommandError> {
+    let rounded = ((ideal / step).round() * step).clamp(MIN_SCALE, MAX_SCALE);
+
+    let mut candidates = vec![rounded];
+    for steps_away in 1..=MAX_STEPS_AWAY {
+        for direction in [-1.0, 1.0] {
+            let candidate = rounded + direction * steps_away as f64 * step;
+            if (MIN_SCALE..=MAX_SCALE).contains(&candidate) {
+                candidates.push(candidate);
+            }
+        }
+    }
+    candidates.sort_by(|a, b| (a - ideal).abs().total_cmp(&(b - ideal).abs()));
+    // Integer scales are supported everywhere
+    candidates.push(ideal.round().max(1.0));
+
+    for candidate in candidates {
+        let mut configuration = Configuration::new();
+        configuration.enable_head(head).set_scale(candidate);
+
+        let result = test(state, event_queue, &configuration)?;
+
+        if result == ConfigResult::Succeeded {
+            return Ok(candidate);
+        }
+    }
+
+    Err(CommandError::ConfigurationFailed(format!(
+        "The compositor did not accept any scale close to {:.2} for display {}",
+        ideal,
+        head.name.clone().unwrap()
+    )))
+}
+
+/// Applies the new scales and moves the displays that were touching the rescaled ones in the same configuration.
+fn apply_scales(
+    state: &mut AppData,
+    event_queue: &mut EventQueue<AppData>,
+    scales: &[(HeadInfo, f64)],
+) -> Result<ConfigResult, CommandError> {
+    let enabled_heads: Vec<HeadInfo> = state
+        .heads
+        .values()
+        .filter(|head| head.enabled && head.position_x.is_some() && head.position_y.is_some())
+        .filter(|head| head.logical_size().is_some())
+        .cloned()
+        .collect();
+    let scale_of = |head: &HeadInfo| {
+        scales
+            .iter()
+            .find(|(scaled, _)| scaled.head == head.head)
+            .map(|(_, scale)| *scale)
+    };
+
+    let before: Vec<Rect> = enabled_heads
+        .iter()
+        .map(|head| {
+            let (width, height) = head.logical_size().unwrap();
+            Rect {
+                x: head.position_x.unwrap(),
+                y: head.position_y.unwrap(),
+                width,
+                height,
+            }
+        })
+        .collect();
+    let new_sizes: Vec<(i32, i32)> = enabled_heads
+        .iter()
+        .map(|head| match scale_of(head) {
+            Some(scale) => {
+                let mode = head.get_current_mode().unwrap();
+                logical_size(mode.width, mode.height, Some(scale), head.transform)
+            }
+            None => head.logical_size().unwrap(),
+        })
+        .collect();
+    let after = rearrange_after_resize(&before, &new_sizes);
+
+    let mut configuration = Configuration::new();
+    for (i, head) in enabled_heads.iter().enumerate() {
+        let scale = scale_of(head);
+        let moved = after[i].x != before[i].x || after[i].y != before[i].y;
+        if scale.is_none() && !moved {
+            continue;
+        }
+
+        let head_config = configuration.enable_head(head);
+        if let Some(scale) = scale {
+            head_config.set_scale(scale);
+        }
+        if moved {
+            head_config.set_position(after[i].x, after[i].y);
+        }
+    }
+
+    apply(state, event_queue, &configuration)
+}
@@ -0,0 +1,61 @@
+use crate::backends::common::dispatch_before;
+use crate::backends::wlroot::connect_trait::WaylandCommand;
+use crate::commands::commands::{CommandError, WaitCommand, WaitCondition};
+use crate::model::{AppData, HeadInfo, HeadModeInput};
+use std::time::Instant;
+use wayland_client::EventQueue;
+
+impl WaitCommand {
+    fn is_reached(&self, state: &AppData) -> bool {
+        let head = state
+            .heads
+            .values()
+            .find(|head_info| head_info.name.as_deref() == Some(self.name.as_str()));
+
+        match (&self.condition, head) {
+            (WaitCondition::Connected, head) => head.is_some(),
+            (WaitCondition::Disconnected, head) => head.is_none(),
+            (_, None) => false,
+            (WaitCondition::Enabled, Some(head)) => head.enabled,
+            (WaitCondition::Disabled, Some(head)) => !head.enabled,
+            (WaitCondition::Mode(mode), Some(head)) => has_current_mode(head, mode),
+        }
+    }
+}
+
+fn has_current_mode(head: &HeadInfo, mode: &HeadModeInput) -> bool {
+    head.enabled
+        && head.get_current_mode().is_some_and(|current| {
+            current.width == mode.width
+                && current.height == mode.height
+                && current.rate == mode.rate
+        })
+}
+
+impl WaylandCommand for WaitCommand {
+    fn run(
+        &self,
+        event_queue: &mut EventQueue<AppData>,
+        state: &mut AppData,
+    ) -> Result<(), CommandError> {
+        let deadline = self.timeout.map(|timeout| Instant::now() + timeout);
+
+        // The state is only consistent once the compositor sent done, the condition is checked then
+        while !self.is_reached(state) {
+            let serial = state.config_serial;
+            while state.config_serial == serial {
+                if !dispatch_before(state, event_queue, deadline)? {
+                    return Err(CommandError::Timeout(format!(
+                        "Display {} is still not {} after {}s",
+                        self.name,
+                        self.condition,
+                        self.timeout.unwrap_or_default().as_secs_f64()
+                    )));
+                }
+            }
+        }
+
+        println!("Display {} is {}", self.name, self.condition);
+        Ok(())
+    }
+}
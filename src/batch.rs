@@ -0,0 +1,88 @@
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+/// Value of the batch file argument that reads the commands from stdin.
+pub static STDIN_SOURCE: &str = "-";
+
+/// A command of a batch file, without the leading `wlout`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BatchLine {
+    pub number: usize,
+    pub text: String,
+    pub words: Vec<String>,
+}
+
+/// Reads the commands of a batch file, skipping blank lines and `#` comments.
+pub fn read_batch_lines(source: &Path) -> Result<Vec<BatchLine>, String> {
+    let content = if source.as_os_str() == STDIN_SOURCE {
+        let mut content = String::new();
+        io::stdin()
+            .read_to_string(&mut content)
+            .map_err(|error| format!("Unable to read the commands from stdin: {}", error))?;
+        content
+    } else {
+        fs::read_to_string(source)
+            .map_err(|error| format!("Unable to read {}: {}", source.display(), error))?
+    };
+
+    let mut lines = vec![];
+    for (i, text) in content.lines().enumerate() {
+        let text = text.trim();
+        if text.is_empty() || text.starts_with('#') {
+            continue;
+        }
+
+        let mut words = split_words(text).map_err(|error| format!("Line {}: {}", i + 1, error))?;
+        // The lines can be copied from a script as is
+        if words.first().map(String::as_str) == Some("wlout") {
+            words.remove(0);
+        }
+
+        lines.push(BatchLine {
+            number: i + 1,
+            text: text.to_string(),
+            words,
+        });
+    }
+
+    Ok(lines)
+}
+
+/// Splits a line on whitespace the way a shell would, honouring single and double quotes.
+pub fn split_words(line: &str) -> Result<Vec<String>, String> {
+    let mut words = vec![];
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote: Option<char> = None;
+
+    for c in line.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => word.push(c),
+            None if c == '\'' || c == '"' => {
+                quote = Some(c);
+                in_word = true;
+            }
+            None if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            None => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+
+    if let Some(q) = quote {
+        return Err(format!("Missing closing quote {}", q));
+    }
+    if in_word {
+        words.push(word);
+    }
+
+    Ok(words)
+}
@@ -1,6 +1,6 @@
 use clap_markdown::help_markdown_command;
-use std::io::{stdout, Write};
-use wlout::cli::build_cli;
+use std::io::{Write, stdout};
+use wlout::build_cli;
 
 fn main() {
     let command = build_cli();
@@ -1,7 +1,7 @@
 use clap::Command;
 use sailfish::TemplateSimple;
 use std::fmt::Write;
-use wlout::cli::build_cli;
+use wlout::build_cli;
 
 #[derive(TemplateSimple)]
 #[template(path = "readme.stpl")]
@@ -31,7 +31,7 @@ fn visit(command: &Command, indent: usize, result: &mut String) {
 }
 
 fn main() {
-    use std::io::{stdout, Write};
+    use std::io::{Write, stdout};
     let command = build_cli();
     let mut result = String::new();
     visit(&command, 0, &mut result);
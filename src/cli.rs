@@ -1,7 +1,11 @@
-use crate::parsers::DisplayModeParser;
+use crate::batch::STDIN_SOURCE;
+use crate::edid::DEFAULT_SYSFS_ROOT;
+use crate::model::{ADAPTIVE_SYNC_STATES, TRANSFORMS};
+use crate::parsers::{DisplayModeParser, SecondsParser, WaitConditionParser};
 use clap::crate_version;
-use clap::{value_parser, Arg, ArgAction, Command};
+use clap::{Arg, ArgAction, Command, value_parser};
 use clap_complete::aot::Shell;
+use std::path::PathBuf;
 
 pub static NAME_ARG_ID: &str = "display";
 
@@ -10,6 +14,25 @@ pub fn build_cli() -> Command {
         .required(true)
         .help("The name of the display");
 
+    let sysfs_root_arg = Arg::new("sysfs_root")
+        .long("sysfs-root")
+        .help(
+            "Where sysfs is mounted, the EDID of the displays is read from <SYSFS_ROOT>/class/drm",
+        )
+        .default_value(DEFAULT_SYSFS_ROOT)
+        .value_parser(value_parser!(PathBuf));
+
+    let target_dpi_arg = Arg::new("target_dpi")
+        .long("target-dpi")
+        .help("Logical DPI to aim for")
+        .value_parser(value_parser!(f64));
+
+    let scale_step_arg = Arg::new("step")
+        .long("step")
+        .help("Scales are multiples of this step")
+        .default_value("0.125")
+        .value_parser(value_parser!(f64));
+
     Command::new("wlout")
         .version(crate_version!())
         .about("Wayland (Wl Roots) Display Manager CLI with UNIX philosophy and modern goodies")
@@ -22,6 +45,85 @@ It is based on the still experimental wlr-output-management-unstable-v1 protocol
 For more information please visit: https://wayland.app/protocols/wlr-output-management-unstable-v1
         ")
         .arg_required_else_help(true)
+        .arg(Arg::new("no_normalize")
+            .long("no-normalize")
+            .help("Apply the positions as given, without moving the top left corner of the layout to (0,0)")
+            .global(true)
+            .action(ArgAction::SetTrue)
+        )
+        .arg(Arg::new("anchor")
+            .long("anchor")
+            .value_name("DISPLAY")
+            .help("Place this display at (0,0) instead of the top left corner of the layout")
+            .global(true)
+            .conflicts_with("no_normalize")
+        )
+        .arg(Arg::new("plan")
+            .long("plan")
+            .help("Print the changes planned for each display before applying them")
+            .global(true)
+            .action(ArgAction::SetTrue)
+        )
+        .arg(Arg::new("strict")
+            .long("strict")
+            .help("Exit with code 3 instead of warning when the compositor applies other settings than the requested ones, eg. a rounded scale")
+            .global(true)
+            .action(ArgAction::SetTrue)
+        )
+        .arg(Arg::new("force_apply")
+            .long("force-apply")
+            .help("Send the configuration even when the displays are already in the requested state")
+            .global(true)
+            .action(ArgAction::SetTrue)
+        )
+        .arg(Arg::new("retries")
+            .long("retries")
+            .help("How many times to send a configuration again when the compositor cancels it because the displays changed meanwhile")
+            .default_value("3")
+            .global(true)
+            .value_parser(value_parser!(u32))
+        )
+        .arg(Arg::new("timeout")
+            .long("timeout")
+            .value_name("SECONDS")
+            .help("Give up with exit code 124 when the compositor does not answer within this many seconds, 5 by default")
+            .global(true)
+            .value_parser(SecondsParser {})
+        )
+        .arg(Arg::new("socket")
+            .long("socket")
+            .value_name("NAME|PATH")
+            .help("Connect to this Wayland socket instead of WAYLAND_DISPLAY, a name in XDG_RUNTIME_DIR like wayland-1 or a path")
+            .global(true)
+            .value_parser(value_parser!(PathBuf))
+        )
+        .arg(Arg::new("all_sockets")
+            .long("all-sockets")
+            .help("Run list or doctor against every wayland-* socket in XDG_RUNTIME_DIR, eg. to query nested compositors")
+            .conflicts_with("socket")
+            .global(true)
+            .action(ArgAction::SetTrue)
+        )
+        .arg(Arg::new("debug")
+            .short('v')
+            .long("debug")
+            .help("Log every protocol event received and request sent on stderr, with timestamps. Must come before the command, like the WLOUT_LOG environment variable set to 1, or to the path of a log file")
+            .action(ArgAction::SetTrue)
+        )
+        .arg(Arg::new("log_file")
+            .long("log-file")
+            .value_name("PATH")
+            .help("Append the protocol log to this file instead of stderr, implies --debug")
+            .global(true)
+            .value_parser(value_parser!(PathBuf))
+        )
+        .arg(Arg::new("force")
+            .short('f')
+            .long("force")
+            .help("Do not prompt before a risky change, eg. turning off every display or setting a custom mode")
+            .global(true)
+            .action(ArgAction::SetTrue)
+        )
         .subcommand(Command::new("list")
             .visible_alias("print")
             .arg(Arg::new("verbose")
@@ -30,11 +132,17 @@ For more information please visit: https://wayland.app/protocols/wlr-output-mana
                      .short('v')
                      .action(ArgAction::SetTrue),
             )
+            .arg(sysfs_root_arg.clone())
             .about("List displays"))
         .subcommand(Command::new("info")
-            .about("Print additional detailed information for a display")
+            .about("Print additional detailed information for a display, including the information read from its EDID")
             .arg_required_else_help(true)
             .arg(display_arg.clone())
+            .arg(sysfs_root_arg.clone())
+            .arg(Arg::new("parsable")
+                .long("parsable")
+                .help("Print the display on one line of tab separated key=value pairs, including its diagonal, PPI, logical DPI and aspect ratio, instead of the tables")
+                .action(ArgAction::SetTrue))
         )
         .subcommand(
             Command::new("power")
@@ -48,11 +156,88 @@ For more information please visit: https://wayland.app/protocols/wlr-output-mana
                              ["on", "off"]
                          ),),
                 )
-                .arg(Arg::new("force")
-                    .short('f')
-                    .long("force")
-                    .help("Do not prompt if the last display is turned off")
-                    .action(ArgAction::SetTrue)
+                .arg(Arg::new("mode")
+                    .long("mode")
+                    .help("Mode to turn the display on with, instead of the one it had when turned off. The format is <WIDTH>x<HEIGHT>@<RATE>")
+                    .value_parser(DisplayModeParser {})
+                )
+                .arg(Arg::new("position")
+                    .long("position")
+                    .help("Position to turn the display on at, instead of the one it had when turned off")
+                    .num_args(2)
+                    .value_names(["X", "Y"])
+                    .allow_negative_numbers(true)
+                    .value_parser(value_parser!(i32))
+                )
+                .arg(Arg::new("scale")
+                    .long("scale")
+                    .help("Scale to turn the display on with, instead of the one it had when turned off")
+                    .value_parser(value_parser!(f64))
+                )
+                .arg(Arg::new("transform")
+                    .long("transform")
+                    .help("Transform to turn the display on with, instead of the one it had when turned off")
+                    .value_parser(clap::builder::PossibleValuesParser::new(
+                        TRANSFORMS.map(|(name, _)| name)
+                    ))
+                )
+                .arg(Arg::new("adaptive_sync")
+                    .long("adaptive-sync")
+                    .help("Adaptive sync (VRR) to turn the display on with, instead of the one it had when turned off")
+                    .value_parser(clap::builder::PossibleValuesParser::new(
+                        ADAPTIVE_SYNC_STATES.map(|(name, _)| name)
+                    ))
+                )
+        )
+        .subcommand(
+            Command::new("only")
+                .about("Turn on a display at (0, 0) and turn off all the other displays.\n
+The displays that were on are remembered and can be turned back on with the all command.")
+                .arg_required_else_help(true)
+                .arg(display_arg.clone())
+        )
+        .subcommand(
+            Command::new("all")
+                .about("Turn on all displays, restoring the ones turned off by the only command where they were")
+        )
+        .subcommand(
+            Command::new("auto")
+                .about("Turn on all displays at their preferred mode with scale 1 and normal transform, arranged from left to right.\n
+Useful to recover from a layout that left the displays unusable.")
+        )
+        .subcommand(
+            Command::new("wait")
+                .about("Block until a display reaches a state, eg. once a dock is plugged in.\n
+Waits forever unless --for is given. Exits with 0 once the state is reached, 124 when the time is up, 1 on error and 2 on usage error.")
+                .arg_required_else_help(true)
+                .arg(display_arg.clone())
+                .arg(Arg::new("condition")
+                    .required(true)
+                    .help("connected, disconnected, enabled, disabled or mode=<WIDTH>x<HEIGHT>@<RATE>")
+                    .value_parser(WaitConditionParser {})
+                )
+                .arg(Arg::new("for")
+                    .long("for")
+                    .value_name("SECONDS")
+                    .help("Give up with exit code 124 when the display has not reached the state within this many seconds")
+                    .value_parser(SecondsParser {})
+                )
+        )
+        .subcommand(
+            Command::new("doctor")
+                .about("Report the socket, the globals offered by the compositor, what the displays report and the known problems, to paste into bug reports")
+        )
+        .subcommand(
+            Command::new("history")
+                .about("List the changes made to the displays, the most recent first")
+        )
+        .subcommand(
+            Command::new("undo")
+                .about("Put the displays back the way they were before a change listed by the history command")
+                .arg(Arg::new("n")
+                    .help("The change to undo, 1 being the most recent one")
+                    .default_value("1")
+                    .value_parser(value_parser!(u64).range(1..))
                 )
         )
         .subcommand(
@@ -123,6 +308,7 @@ Applies position optimisation after moving to ensure that one of your displays i
             Command::new("mode")
                 .about("Manage the display mode resolution and refresh rate")
                 .arg(display_arg.clone())
+                .subcommand_negates_reqs(true)
                 .subcommand(Command::new("list")
                     .visible_alias("print")
                     .about("List the available modes for a display"))
@@ -140,13 +326,59 @@ Applies position optimisation after moving to ensure that one of your displays i
                             .help("The mode format is <WIDTH>x<HEIGHT>@<RATE>")
                             .value_parser(DisplayModeParser {}),
                     )
+                )
+                .subcommand(Command::new("calc")
+                    .arg_required_else_help(true)
+                    .about("Compute the CVT and CVT reduced blanking timings of a mode. Does not need a display: wlout mode calc <mode>")
+                    .arg(
+                        Arg::new("mode")
+                            .required(true)
+                            .help("The mode format is <WIDTH>x<HEIGHT>@<RATE>")
+                            .value_parser(DisplayModeParser {}),
+                    )
+                )
+        )
+        .subcommand(
+            Command::new("scale")
+                .about("Pick the display scale from its density")
+                .subcommand_required(true)
+                .subcommand_negates_reqs(true)
+                .arg(display_arg.clone())
+                .subcommand(Command::new("auto")
+                    .about("Set the scale giving the closest logical DPI to the target. Displays touching it are moved to follow its new size")
+                    .arg(target_dpi_arg.clone().default_value("96"))
+                    .arg(scale_step_arg.clone())
+                )
+                .subcommand(Command::new("equalize")
+                    .about("Set the scales so that text looks the same physical size on every display. Does not take a display before the command: wlout scale equalize <displays>...")
                     .arg(
-                        Arg::new("force")
-                            .short('f')
-                            .long("force")
-                            .action(ArgAction::SetTrue)
-                            .help("Do not prompt when setting a custom mode.")
+                        Arg::new("displays")
+                            .required(true)
+                            .num_args(2..)
+                            .help("The names of the displays")
                     )
+                    .arg(target_dpi_arg.clone()
+                        .help("Logical DPI to aim for. Defaults to the density of the least dense display"))
+                    .arg(scale_step_arg.clone())
+                )
+        )
+        .subcommand(
+            Command::new("probe")
+                .about("Find out which scales, transforms, adaptive sync states and refresh rates above the advertised ones the compositor accepts for a display.\n
+Every value is sent as a test configuration, nothing is changed.")
+                .arg_required_else_help(true)
+                .arg(display_arg.clone())
+                .arg(scale_step_arg.clone().help("Scales tested are multiples of this step"))
+                .arg(Arg::new("rate_step")
+                    .long("rate-step")
+                    .help("Refresh rates above the advertised maximum are tested by steps of this many Hz")
+                    .default_value("5")
+                    .value_parser(value_parser!(i32).range(1..))
+                )
+                .arg(Arg::new("max_rate")
+                    .long("max-rate")
+                    .help("Highest refresh rate tested, defaults to 60 Hz above the advertised maximum")
+                    .value_parser(value_parser!(i32).range(1..))
                 )
         )
         .subcommand(
@@ -164,6 +396,27 @@ Applies position optimisation after moving to ensure that one of your displays i
                         )
                 )
         )
+        .subcommand(
+            Command::new("batch")
+                .about("Run the commands of a file, one per line, over a single connection to the compositor.\n
+Each line uses the same syntax as the command line, without the leading wlout. Blank lines and lines starting with # are ignored.
+The global options apply to the whole batch and go before batch, a line can only give --force for its own command.")
+                .arg(Arg::new("file")
+                    .help("File to read the commands from, - for stdin")
+                    .default_value(STDIN_SOURCE)
+                    .value_parser(value_parser!(PathBuf))
+                )
+                .arg(Arg::new("merge")
+                    .long("merge")
+                    .help("Send the changes of every command in a single configuration once they all ran. The commands see the displays as they were before the batch")
+                    .action(ArgAction::SetTrue)
+                )
+                .arg(Arg::new("keep_going")
+                    .long("keep-going")
+                    .help("Skip the invalid or failing commands instead of stopping at the first one")
+                    .action(ArgAction::SetTrue)
+                )
+        )
         .subcommand(
             Command::new("completion")
                 .about("Generate shell completion script. Dynamic completion for display names and modes is currently available for Zsh and Bash")
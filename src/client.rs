@@ -0,0 +1,480 @@
+use crate::backends::common::{
+    applied_differences, head_states, leaves_a_display_on, prepare_configuration, roundtrip,
+    send_configuration,
+};
+use crate::backends::wlroot::connect_trait::{connect_to_output_manager, stop_output_manager};
+use crate::commands::commands::{CommandError, GlobalOptions};
+use crate::configuration::Configuration;
+use crate::history::{HistoryEntry, record_history_entry};
+use crate::model::{AppData, ConfigResult, HeadInfo, HeadModeInput, HeadState};
+use std::env;
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+use std::time::Duration;
+use wayland_client::EventQueue;
+use wayland_client::protocol::wl_output::Transform;
+use wayland_protocols_wlr::output_management::v1::client::zwlr_output_head_v1::AdaptiveSyncState;
+
+/// Why a call of the client failed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// No compositor to connect to, it has no wlroots output manager, or the connection was lost.
+    Connection(String),
+    /// The compositor did not answer in time.
+    Timeout(String),
+    /// No connected display has this name.
+    UnknownDisplay(String),
+    /// The configuration is refused before being sent, eg. because it turns off every display.
+    InvalidConfiguration(String),
+    /// The compositor rejected the configuration.
+    Rejected,
+    /// The displays changed while the configuration was sent, it can be sent again.
+    Cancelled,
+    /// The configuration was applied, but the compositor changed some settings, eg. rounded a scale.
+    NotApplied(Vec<String>),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Connection(message) => write!(f, "{}", message),
+            Error::Timeout(message) => write!(f, "{}", message),
+            Error::UnknownDisplay(name) => write!(f, "Display \"{}\" not found", name),
+            Error::InvalidConfiguration(message) => write!(f, "{}", message),
+            Error::Rejected => write!(f, "The compositor rejected the configuration"),
+            Error::Cancelled => write!(f, "The displays changed while the configuration was sent"),
+            Error::NotApplied(differences) => write!(f, "{}", differences.join("\n")),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl Error {
+    fn from_command(error: CommandError) -> Self {
+        match error {
+            CommandError::ConnectionLost(message) => Error::Connection(message),
+            CommandError::Timeout(message) => Error::Timeout(message),
+            CommandError::ConfigurationFailed(_) => Error::Rejected,
+            CommandError::ConfigurationCancelled => Error::Cancelled,
+            CommandError::NotApplied(message) => {
+                Error::NotApplied(message.lines().map(String::from).collect())
+            }
+            other => Error::InvalidConfiguration(other.to_string()),
+        }
+    }
+}
+
+/// How to connect to the compositor, `ConnectOptions::default()` connecting to `WAYLAND_DISPLAY`.
+#[derive(Debug, Clone, Default)]
+pub struct ConnectOptions {
+    /// Wayland socket to connect to instead of `WAYLAND_DISPLAY`, a name in `XDG_RUNTIME_DIR` or a path.
+    pub socket: Option<PathBuf>,
+    /// How long to wait for each answer of the compositor, 5 seconds when not given.
+    pub timeout: Option<Duration>,
+    /// Keep the positions as given instead of moving the layout to the origin.
+    pub no_normalize: bool,
+    /// Allow applying a configuration that turns off every display.
+    pub allow_all_off: bool,
+}
+
+impl ConnectOptions {
+    fn to_global_options(&self) -> GlobalOptions {
+        GlobalOptions {
+            socket: self.socket.clone(),
+            timeout: self.timeout,
+            no_normalize: self.no_normalize,
+            force: self.allow_all_off,
+            ..GlobalOptions::default()
+        }
+    }
+}
+
+/// A connection to the output manager of the compositor, stopped when dropped.
+pub struct Client {
+    event_queue: EventQueue<AppData>,
+    state: AppData,
+}
+
+/// A display as last described by the compositor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Head {
+    pub name: String,
+    pub description: Option<String>,
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub serial: Option<String>,
+    pub enabled: bool,
+    /// Advertised modes, the biggest and fastest first.
+    pub modes: Vec<Mode>,
+    pub position: Option<(i32, i32)>,
+    pub scale: Option<f64>,
+    pub transform: Option<Transform>,
+    pub adaptive_sync: Option<bool>,
+    /// Physical width and height in millimeters.
+    pub physical_size: Option<(i32, i32)>,
+}
+
+/// A mode advertised by a display, the refresh rate in Hz.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Mode {
+    pub width: i32,
+    pub height: i32,
+    pub rate: i32,
+    pub preferred: bool,
+    pub current: bool,
+}
+
+impl Head {
+    pub fn current_mode(&self) -> Option<&Mode> {
+        self.modes.iter().find(|mode| mode.current)
+    }
+
+    pub fn preferred_mode(&self) -> Option<&Mode> {
+        self.modes.iter().find(|mode| mode.preferred)
+    }
+}
+
+impl From<&HeadInfo> for Head {
+    fn from(head: &HeadInfo) -> Self {
+        let mut modes: Vec<Mode> = head
+            .modes
+            .values()
+            .map(|mode| Mode {
+                width: mode.width,
+                height: mode.height,
+                rate: mode.rate,
+                preferred: mode.is_preferred,
+                current: mode.is_current,
+            })
+            .collect();
+        modes.sort_by_key(|mode| std::cmp::Reverse((mode.width * mode.height, mode.rate)));
+
+        Head {
+            name: head.name.clone().unwrap_or_default(),
+            description: head.description.clone(),
+            make: head.make.clone(),
+            model: head.model.clone(),
+            serial: head.serial.clone(),
+            enabled: head.enabled,
+            modes,
+            position: head.position_x.zip(head.position_y),
+            scale: head.scale,
+            transform: head.transform,
+            adaptive_sync: head
+                .adaptive_sync
+                .map(|state| state == AdaptiveSyncState::Enabled),
+            physical_size: head.physical_width.zip(head.physical_height),
+        }
+    }
+}
+
+/// Changes staged for several displays by name, sent together by `Client::test` or `Client::apply`.
+/// Setting any property of a display turns it on, the displays left out keep their current state.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigurationBuilder {
+    heads: Vec<HeadState>,
+}
+
+impl ConfigurationBuilder {
+    pub fn new() -> Self {
+        ConfigurationBuilder::default()
+    }
+
+    pub fn enable(mut self, name: &str) -> Self {
+        self.staged(name);
+        self
+    }
+
+    /// Turns the display off, dropping the changes staged for it so far.
+    pub fn disable(mut self, name: &str) -> Self {
+        *self.staged(name) = HeadState {
+            enabled: false,
+            ..unchanged(name)
+        };
+        self
+    }
+
+    /// Uses the advertised mode when there is one, or else a custom mode.
+    pub fn mode(mut self, name: &str, width: i32, height: i32, rate: i32) -> Self {
+        self.staged(name).mode = Some(HeadModeInput {
+            width,
+            height,
+            rate,
+        });
+        self
+    }
+
+    pub fn position(mut self, name: &str, x: i32, y: i32) -> Self {
+        self.staged(name).position = Some((x, y));
+        self
+    }
+
+    pub fn scale(mut self, name: &str, scale: f64) -> Self {
+        self.staged(name).scale = Some(scale);
+        self
+    }
+
+    pub fn transform(mut self, name: &str, transform: Transform) -> Self {
+        self.staged(name).transform = Some(transform);
+        self
+    }
+
+    pub fn adaptive_sync(mut self, name: &str, enabled: bool) -> Self {
+        self.staged(name).adaptive_sync = Some(match enabled {
+            true => AdaptiveSyncState::Enabled,
+            false => AdaptiveSyncState::Disabled,
+        });
+        self
+    }
+
+    /// The staged state of the display, turned on since a property is about to be set.
+    fn staged(&mut self, name: &str) -> &mut HeadState {
+        let index = match self.heads.iter().position(|head| head.name == name) {
+            Some(index) => index,
+            None => {
+                self.heads.push(unchanged(name));
+                self.heads.len() - 1
+            }
+        };
+
+        let head = &mut self.heads[index];
+        head.enabled = true;
+        head
+    }
+
+    fn build(&self, state: &AppData) -> Result<Configuration, Error> {
+        let mut configuration = Configuration::new();
+        for staged in &self.heads {
+            let head = state
+                .get_head(&staged.name)
+                .map_err(|_| Error::UnknownDisplay(staged.name.clone()))?;
+            configuration.set_head_state(&head, staged);
+        }
+
+        Ok(configuration)
+    }
+}
+
+/// A display turned on without any property change.
+fn unchanged(name: &str) -> HeadState {
+    HeadState {
+        name: name.to_string(),
+        make: None,
+        model: None,
+        serial: None,
+        enabled: true,
+        mode: None,
+        refresh: None,
+        position: None,
+        scale: None,
+        transform: None,
+        adaptive_sync: None,
+    }
+}
+
+impl Client {
+    /// Connects to the compositor of `WAYLAND_DISPLAY` with the default options.
+    pub fn connect() -> Result<Client, Error> {
+        Client::connect_with(&ConnectOptions::default())
+    }
+
+    /// Connects with other options than the default ones, eg. to another socket.
+    pub fn connect_with(options: &ConnectOptions) -> Result<Client, Error> {
+        let (event_queue, state) = connect_to_output_manager(&options.to_global_options())
+            .map_err(|error| match error {
+                CommandError::InvalidRequest(message) => Error::Connection(message),
+                other => Error::from_command(other),
+            })?;
+
+        Ok(Client { event_queue, state })
+    }
+
+    /// The connected displays sorted by name, as of the last call to `connect`, `refresh` or `apply`.
+    pub fn heads(&self) -> Vec<Head> {
+        let mut heads: Vec<Head> = self.state.heads.values().map(Head::from).collect();
+        heads.sort_by(|a, b| a.name.cmp(&b.name));
+
+        heads
+    }
+
+    /// Receives the changes the compositor sent since the last call, eg. a display plugged in.
+    pub fn refresh(&mut self) -> Result<(), Error> {
+        roundtrip(&mut self.state, &mut self.event_queue).map_err(Error::from_command)
+    }
+
+    /// Asks the compositor whether it would accept the configuration, without changing anything.
+    pub fn test(&mut self, configuration: &ConfigurationBuilder) -> Result<(), Error> {
+        let prepared = self.prepare(configuration)?;
+
+        self.send(&prepared, true)
+    }
+
+    /// Applies the configuration, the heads are up to date once it returns. The change is recorded in the
+    /// history of `wlout undo`.
+    ///
+    /// A configuration turning off every display is refused unless `allow_all_off` is set in the options.
+    /// When the compositor applies other settings than the requested ones, eg. a rounded scale, the
+    /// configuration stays applied and the differences are returned as `Error::NotApplied`.
+    pub fn apply(&mut self, configuration: &ConfigurationBuilder) -> Result<(), Error> {
+        let prepared = self.prepare(configuration)?;
+        if !leaves_a_display_on(&prepared) && !self.state.options.force {
+            return Err(Error::InvalidConfiguration(String::from(
+                "The configuration would turn off every display",
+            )));
+        }
+
+        let before = head_states(&self.state);
+        self.send(&prepared, false)?;
+
+        // The compositor sends the new state of the heads once it is done applying
+        self.refresh()?;
+
+        let entry = HistoryEntry {
+            command: env::args().collect::<Vec<String>>().join(" "),
+            ..HistoryEntry::new(&before, &head_states(&self.state))
+        };
+        // The history is only a convenience, failing to write it must not fail the configuration applied
+        if !entry.is_empty() {
+            let _ = record_history_entry(entry);
+        }
+
+        let differences = applied_differences(&self.state, &prepared);
+        if !differences.is_empty() {
+            return Err(Error::NotApplied(differences));
+        }
+
+        Ok(())
+    }
+
+    fn prepare(&self, configuration: &ConfigurationBuilder) -> Result<Configuration, Error> {
+        prepare_configuration(&self.state, &configuration.build(&self.state)?)
+            .map_err(Error::from_command)
+    }
+
+    fn send(&mut self, prepared: &Configuration, test_only: bool) -> Result<(), Error> {
+        let result =
+            send_configuration(&mut self.state, &mut self.event_queue, prepared, test_only)
+                .map_err(Error::from_command)?;
+
+        match result {
+            ConfigResult::Succeeded => Ok(()),
+            ConfigResult::Failed => Err(Error::Rejected),
+            ConfigResult::Cancelled => Err(Error::Cancelled),
+        }
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        stop_output_manager(&mut self.event_queue, &mut self.state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::HeadInfo;
+    use std::collections::HashMap;
+    use std::os::unix::net::UnixStream;
+    use wayland_client::{Connection, Proxy};
+    use wayland_protocols_wlr::output_management::v1::client::zwlr_output_head_v1::ZwlrOutputHeadV1;
+
+    /// The state of a client connected to a compositor with a single display on, nothing is ever sent.
+    fn state_with_head(name: &str) -> AppData {
+        let (socket, _) = UnixStream::pair().unwrap();
+        let connection = Connection::from_socket(socket).unwrap();
+
+        let mut head = HeadInfo::new(ZwlrOutputHeadV1::inert(connection.backend().downgrade()));
+        head.name = Some(name.to_string());
+        head.enabled = true;
+        head.position_x = Some(0);
+        head.position_y = Some(0);
+        head.scale = Some(1.0);
+
+        AppData {
+            connection,
+            initial_done: true,
+            heads: HashMap::from([(head.head.id(), head)]),
+            manager: None,
+            config_result: None,
+            config_serial: Some(1),
+            output_manager_found: true,
+            manager_version: 4,
+            globals: vec![],
+            options: GlobalOptions::default(),
+            batch_configuration: None,
+            batch_saved_states: vec![],
+        }
+    }
+
+    #[test]
+    fn disable_drops_the_staged_changes() {
+        let builder = ConfigurationBuilder::new()
+            .enable("DP-1")
+            .scale("DP-1", 1.5)
+            .disable("DP-1");
+
+        assert_eq!(builder.heads.len(), 1);
+        assert!(!builder.heads[0].enabled);
+        assert_eq!(builder.heads[0].scale, None);
+
+        let configuration = builder.build(&state_with_head("DP-1")).unwrap();
+        assert!(!configuration.heads()[0].enabled);
+        assert_eq!(configuration.heads()[0].scale, None);
+    }
+
+    #[test]
+    fn setting_a_property_turns_the_display_on() {
+        let builder = ConfigurationBuilder::new()
+            .disable("DP-1")
+            .scale("DP-1", 2.0);
+
+        assert!(builder.heads[0].enabled);
+
+        let configuration = builder.build(&state_with_head("DP-1")).unwrap();
+        assert!(configuration.heads()[0].enabled);
+        assert_eq!(configuration.heads()[0].scale, Some(2.0));
+        assert_eq!(configuration.heads()[0].position, None);
+    }
+
+    #[test]
+    fn unknown_display_is_an_error() {
+        let builder = ConfigurationBuilder::new().position("HDMI-A-1", 1920, 0);
+
+        assert_eq!(
+            builder.build(&state_with_head("DP-1")).err(),
+            Some(Error::UnknownDisplay(String::from("HDMI-A-1")))
+        );
+    }
+
+    #[test]
+    fn turning_off_every_display_is_refused() {
+        let state = state_with_head("DP-1");
+        let mut client = Client {
+            event_queue: state.connection.new_event_queue(),
+            state,
+        };
+
+        let error = client
+            .apply(&ConfigurationBuilder::new().disable("DP-1"))
+            .unwrap_err();
+        assert!(
+            matches!(error, Error::InvalidConfiguration(_)),
+            "{:?}",
+            error
+        );
+    }
+
+    #[test]
+    fn differences_of_the_applied_configuration_are_kept_apart() {
+        let error = Error::from_command(CommandError::NotApplied(String::from(
+            "Scale of display DP-1 is 1.49609375 instead of the requested 1.5\nMode of display DP-2 is N/A",
+        )));
+
+        let Error::NotApplied(differences) = error else {
+            panic!("NotApplied expected, got {:?}", error)
+        };
+        assert_eq!(differences.len(), 2);
+    }
+}
@@ -1,13 +1,115 @@
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+use std::time::Duration;
+
 pub trait Executable {
-    fn execute(&self);
+    fn execute(&self, options: &GlobalOptions);
+}
+
+/// Why a command did not complete, printed on stderr before exiting with a non zero code.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandError {
+    /// The command cannot be run as requested, eg. the display is off or the arguments do not make sense.
+    InvalidRequest(String),
+    /// The compositor refused the configuration.
+    ConfigurationFailed(String),
+    /// The compositor cancelled the configuration because the displays changed in the meantime.
+    ConfigurationCancelled,
+    /// The user did not confirm a prompt.
+    Aborted,
+    /// Some commands of a batch failed, their errors were already printed.
+    BatchFailed { failed: usize, total: usize },
+    /// The connection to the compositor was lost or a protocol error occurred.
+    ConnectionLost(String),
+    /// The expected state was not reached in time.
+    Timeout(String),
+    /// The compositor applied the configuration with settings other than the requested ones.
+    NotApplied(String),
+}
+
+/// Exit code of a command that gave up waiting, the same as timeout(1). Usage errors already exit with 2.
+pub const TIMEOUT_EXIT_CODE: i32 = 124;
+/// Exit code under `--strict` when the compositor did not apply the settings exactly as requested.
+pub const NOT_APPLIED_EXIT_CODE: i32 = 3;
+
+impl CommandError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CommandError::Timeout(_) => TIMEOUT_EXIT_CODE,
+            CommandError::NotApplied(_) => NOT_APPLIED_EXIT_CODE,
+            _ => 1,
+        }
+    }
+}
+
+impl Display for CommandError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CommandError::InvalidRequest(message) => write!(f, "{}", message),
+            CommandError::ConfigurationFailed(message) => write!(f, "{}", message),
+            CommandError::ConfigurationCancelled => write!(f, "Configuration cancelled"),
+            CommandError::Aborted => write!(f, "Aborted"),
+            CommandError::BatchFailed { failed, total } => {
+                write!(
+                    f,
+                    "{} of the {} commands of the batch failed",
+                    failed, total
+                )
+            }
+            CommandError::ConnectionLost(message) => {
+                write!(f, "Lost the connection to the compositor: {}", message)
+            }
+            CommandError::Timeout(message) => write!(f, "{}", message),
+            CommandError::NotApplied(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+/// Options given before the command name, shared by every command.
+#[derive(Debug, Clone, Default)]
+pub struct GlobalOptions {
+    /// Keep the positions as requested instead of moving the layout to the origin.
+    pub no_normalize: bool,
+    /// Display placed at the origin instead of the top left corner of the layout.
+    pub anchor: Option<String>,
+    /// Do not ask before a risky change, and make it even when nobody can be asked.
+    pub force: bool,
+    /// Print the changes planned for each display before applying them.
+    pub plan: bool,
+    /// Fail when the compositor applies other settings than the requested ones, instead of warning.
+    pub strict: bool,
+    /// Send the configuration even when it would not change anything.
+    pub force_apply: bool,
+    /// How many times a configuration cancelled because the displays changed meanwhile is sent again.
+    pub retries: u32,
+    /// How long to wait for the compositor, `DEFAULT_TIMEOUT` when not given.
+    pub timeout: Option<Duration>,
+    /// Wayland socket to connect to instead of `WAYLAND_DISPLAY`, a name in `XDG_RUNTIME_DIR` or a path.
+    pub socket: Option<PathBuf>,
+    /// Run the query against every compositor socket found in `XDG_RUNTIME_DIR`, only for read-only commands.
+    pub all_sockets: bool,
+}
+
+/// How long the compositor has to answer when no `--timeout` is given.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+impl GlobalOptions {
+    /// How long to wait for each answer of the compositor.
+    pub fn protocol_timeout(&self) -> Duration {
+        self.timeout.unwrap_or(DEFAULT_TIMEOUT)
+    }
 }
 
 pub struct InfoCommand {
     pub name: String,
+    pub sysfs_root: PathBuf,
+    /// Print the display as one line of `key=value` pairs for scripts, without the EDID.
+    pub parsable: bool,
 }
 
 pub struct ListCommand {
     pub verbose: bool,
+    pub sysfs_root: PathBuf,
 }
 
 pub struct MirrorCommand {
@@ -15,6 +117,8 @@ pub struct MirrorCommand {
     pub reference_display_name: String,
 }
 use crate::model::HeadModeInput;
+use wayland_client::protocol::wl_output::Transform;
+use wayland_protocols_wlr::output_management::v1::client::zwlr_output_head_v1::AdaptiveSyncState;
 
 pub struct ModeCurrentCommand {
     pub name: String,
@@ -31,13 +135,16 @@ pub struct ModePreferredCommand {
 pub struct ModeSetCommand {
     pub name: String,
     pub mode: HeadModeInput,
-    pub force: bool,
 }
 
 pub struct ModeListCommand {
     pub name: String,
 }
 
+pub struct ModeCalcCommand {
+    pub mode: HeadModeInput,
+}
+
 pub const REL_POS_ABOVE: &str = "above";
 pub const REL_POS_BELOW: &str = "below";
 pub const REL_POS_LEFT_OF: &str = "left-of";
@@ -55,8 +162,85 @@ pub struct MoveCommand {
     pub y: i32,
 }
 
+/// When turning a display on, the settings it had when it was turned off are restored.
+/// Any of them can be overridden.
 pub struct PowerCommand {
     pub name: String,
     pub on: bool,
-    pub force: bool
-}
\ No newline at end of file
+    pub mode: Option<HeadModeInput>,
+    pub position: Option<(i32, i32)>,
+    pub scale: Option<f64>,
+    pub transform: Option<Transform>,
+    pub adaptive_sync: Option<AdaptiveSyncState>,
+}
+
+pub struct OnlyCommand {
+    pub name: String,
+}
+
+pub struct AllCommand {}
+
+pub struct AutoCommand {}
+
+pub struct ScaleAutoCommand {
+    pub name: String,
+    pub target_dpi: f64,
+    pub step: f64,
+}
+
+pub struct ScaleEqualizeCommand {
+    pub names: Vec<String>,
+    pub target_dpi: Option<f64>,
+    pub step: f64,
+}
+
+/// Finds out with test configurations what the compositor accepts for a display, without changing it.
+pub struct ProbeCommand {
+    pub name: String,
+    pub scale_step: f64,
+    /// Refresh rates above the advertised maximum are tested by steps of this many Hz.
+    pub rate_step: i32,
+    /// Highest refresh rate tested, defaults to a bit above the advertised maximum.
+    pub max_rate: Option<i32>,
+}
+
+/// Reports the connection, the protocols offered by the compositor and its known problems, for bug reports.
+pub struct DoctorCommand {}
+
+pub struct HistoryCommand {}
+
+/// Restores the displays as they were before the nth most recent change, 1 being the last one.
+pub struct UndoCommand {
+    pub index: usize,
+}
+
+/// State of a display awaited by the wait command.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WaitCondition {
+    Connected,
+    Disconnected,
+    Enabled,
+    Disabled,
+    /// The display is on with this mode as its current one.
+    Mode(HeadModeInput),
+}
+
+impl Display for WaitCondition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WaitCondition::Connected => write!(f, "connected"),
+            WaitCondition::Disconnected => write!(f, "disconnected"),
+            WaitCondition::Enabled => write!(f, "enabled"),
+            WaitCondition::Disabled => write!(f, "disabled"),
+            WaitCondition::Mode(mode) => write!(f, "mode={}", mode),
+        }
+    }
+}
+
+/// Blocks until the display reaches the condition, or gives up after the timeout when one is given.
+pub struct WaitCommand {
+    pub name: String,
+    pub condition: WaitCondition,
+    /// How long to wait for the condition with `--for`, forever when not given.
+    pub timeout: Option<Duration>,
+}
@@ -114,7 +114,8 @@ __wlout_ci_compadd() {
         script = script.replace("<display>", "$(__wlout_list_displays)");
         script = script.replace("<other_display>", "$(__wlout_list_displays)");
         script = script.replace("[mode]", "$(__wlout_list_modes)");
-        script = script.replace(r#"        wlout__move)
+        script = script.replace(
+            r#"        wlout__move)
             opts="-h --help $(__wlout_list_displays) above below right-of left-of position help"
             if [[ ${cur} == -* || ${COMP_CWORD} -eq 2 ]] ; then
                 COMPREPLY=( $(compgen -W "${opts}" -- "${cur}") )
@@ -128,7 +129,8 @@ __wlout_ci_compadd() {
             COMPREPLY=( $(compgen -W "${opts}" -- "${cur}") )
             return 0
             ;;
-"#, r#"        wlout__move)
+"#,
+            r#"        wlout__move)
             local displays_raw="$(__wlout_list_displays)"
             local -a displays
             read -a displays <<<"${displays_raw}"
@@ -149,7 +151,8 @@ __wlout_ci_compadd() {
             esac
             return 0
             ;;
-"#,);
+"#,
+        );
         script = script.replace(
             r#"        wlout__info)
             opts="-h --help $(__wlout_list_displays)"
@@ -166,7 +169,7 @@ __wlout_ci_compadd() {
             return 0
             ;;
 "#,
-r#"        wlout__info)
+            r#"        wlout__info)
             if [[ ${cur} == -* ]]; then
                 COMPREPLY=( $(compgen -W "-h --help" -- "${cur}") )
                 return 0
@@ -199,7 +202,7 @@ r#"        wlout__info)
             return 0
             ;;
 "#,
-r#"        wlout__power)
+            r#"        wlout__power)
             local displays_raw="$(__wlout_list_displays)"
             local -a displays
             read -a displays <<<"${displays_raw}"
@@ -233,7 +236,7 @@ r#"        wlout__power)
             return 0
             ;;
 "#,
-r#"        wlout__mirror)
+            r#"        wlout__mirror)
             local displays_raw="$(__wlout_list_displays)"
             local -a displays
             read -a displays <<<"${displays_raw}"
@@ -268,7 +271,7 @@ r#"        wlout__mirror)
             return 0
             ;;
 "#,
-r#"        wlout__mirror__same__as)
+            r#"        wlout__mirror__same__as)
             if [[ ${cur} == -* ]]; then
                 COMPREPLY=( $(compgen -W "-h --help" -- "${cur}") )
                 return 0
@@ -333,7 +336,7 @@ r#"        wlout__mirror__same__as)
             return 0
             ;;
 "#,
-r#"        wlout__mode__set)
+            r#"        wlout__mode__set)
             if [[ ${cur} == -* ]]; then
                 COMPREPLY=( $(compgen -W "-h --help" -- "${cur}") )
                 return 0
@@ -0,0 +1,359 @@
+use crate::layout::Rect;
+use crate::model::{HeadInfo, HeadMode, HeadModeInput, HeadState, logical_size};
+use wayland_client::protocol::wl_output::Transform;
+use wayland_protocols_wlr::output_management::v1::client::zwlr_output_head_v1::AdaptiveSyncState;
+
+#[derive(Debug, Clone)]
+pub enum ModeSetting {
+    /// One of the modes advertised by the head.
+    Advertised(HeadMode),
+    /// A mode the head does not advertise, with its refresh rate in Hz.
+    Custom(HeadModeInput),
+}
+
+impl ModeSetting {
+    pub fn size(&self) -> (i32, i32) {
+        match self {
+            ModeSetting::Advertised(mode) => (mode.width, mode.height),
+            ModeSetting::Custom(mode) => (mode.width, mode.height),
+        }
+    }
+
+    pub fn to_input(&self) -> HeadModeInput {
+        match self {
+            ModeSetting::Advertised(mode) => HeadModeInput {
+                width: mode.width,
+                height: mode.height,
+                rate: mode.rate,
+            },
+            ModeSetting::Custom(mode) => mode.clone(),
+        }
+    }
+
+    /// The refresh rate in mHz, the one sent to the compositor.
+    pub fn refresh(&self) -> i32 {
+        match self {
+            ModeSetting::Advertised(mode) => mode.refresh,
+            ModeSetting::Custom(mode) => mode.rate * 1000,
+        }
+    }
+}
+
+/// The settings requested for one head, the ones left to `None` are kept as they are.
+#[derive(Debug, Clone)]
+pub struct HeadConfiguration {
+    pub head: HeadInfo,
+    pub enabled: bool,
+    pub mode: Option<ModeSetting>,
+    pub position: Option<(i32, i32)>,
+    pub scale: Option<f64>,
+    pub transform: Option<Transform>,
+    pub adaptive_sync: Option<AdaptiveSyncState>,
+}
+
+impl HeadConfiguration {
+    fn new(head: &HeadInfo, enabled: bool) -> Self {
+        HeadConfiguration {
+            head: head.clone(),
+            enabled,
+            mode: None,
+            position: None,
+            scale: None,
+            transform: None,
+            adaptive_sync: None,
+        }
+    }
+
+    pub fn set_mode(&mut self, mode: &HeadMode) -> &mut Self {
+        self.mode = Some(ModeSetting::Advertised(mode.clone()));
+        self
+    }
+
+    pub fn set_custom_mode(&mut self, mode: &HeadModeInput) -> &mut Self {
+        self.mode = Some(ModeSetting::Custom(mode.clone()));
+        self
+    }
+
+    pub fn set_position(&mut self, x: i32, y: i32) -> &mut Self {
+        self.position = Some((x, y));
+        self
+    }
+
+    pub fn set_scale(&mut self, scale: f64) -> &mut Self {
+        self.scale = Some(scale);
+        self
+    }
+
+    pub fn set_transform(&mut self, transform: Transform) -> &mut Self {
+        self.transform = Some(transform);
+        self
+    }
+
+    pub fn set_adaptive_sync(&mut self, adaptive_sync: AdaptiveSyncState) -> &mut Self {
+        self.adaptive_sync = Some(adaptive_sync);
+        self
+    }
+
+    /// The state the head is expected to be in once the configuration is applied.
+    pub fn resulting_state(&self) -> HeadState {
+        self.applied_to(HeadState::from(&self.head))
+    }
+
+    /// The requested settings on top of a state of the head, the others are kept as they are.
+    pub fn applied_to(&self, state: HeadState) -> HeadState {
+        if !self.enabled {
+            return HeadState {
+                enabled: false,
+                ..state
+            };
+        }
+
+        HeadState {
+            enabled: true,
+            mode: self.mode.as_ref().map(ModeSetting::to_input).or(state.mode),
+            refresh: self
+                .mode
+                .as_ref()
+                .map(ModeSetting::refresh)
+                .or(state.refresh),
+            position: self.position.or(state.position),
+            scale: self.scale.or(state.scale),
+            transform: self.transform.or(state.transform),
+            adaptive_sync: self.adaptive_sync.or(state.adaptive_sync),
+            ..state
+        }
+    }
+
+    /// Where the head ends up in the global compositor space once the configuration is applied.
+    /// `None` when it is turned off or when its position or size is unknown.
+    pub fn resulting_rect(&self) -> Option<Rect> {
+        if !self.enabled {
+            return None;
+        }
+
+        let (x, y) = self
+            .position
+            .or_else(|| self.head.position_x.zip(self.head.position_y))?;
+        let (width, height) = self.mode.as_ref().map(ModeSetting::size).or_else(|| {
+            self.head
+                .get_current_or_preferred_mode()
+                .map(|mode| (mode.width, mode.height))
+        })?;
+        let (width, height) = logical_size(
+            width,
+            height,
+            self.scale.or(self.head.scale),
+            self.transform.or(self.head.transform),
+        );
+
+        Some(Rect {
+            x,
+            y,
+            width,
+            height,
+        })
+    }
+}
+
+/// Every change sent to the compositor in a single configuration.
+#[derive(Debug, Clone, Default)]
+pub struct Configuration {
+    heads: Vec<HeadConfiguration>,
+}
+
+impl Configuration {
+    pub fn new() -> Self {
+        Configuration::default()
+    }
+
+    pub fn heads(&self) -> &[HeadConfiguration] {
+        &self.heads
+    }
+
+    pub fn get(&self, head: &HeadInfo) -> Option<&HeadConfiguration> {
+        self.heads
+            .iter()
+            .find(|configured| configured.head.head == head.head)
+    }
+
+    /// Turns the head on, keeping the settings already requested for it.
+    pub fn enable_head(&mut self, head: &HeadInfo) -> &mut HeadConfiguration {
+        let index = self.index_of(head);
+        let head_configuration = &mut self.heads[index];
+        head_configuration.enabled = true;
+
+        head_configuration
+    }
+
+    pub fn disable_head(&mut self, head: &HeadInfo) {
+        let index = self.index_of(head);
+        self.heads[index] = HeadConfiguration::new(head, false);
+    }
+
+    /// Enables or disables the head and requests every property recorded in a saved state.
+    pub fn set_head_state(&mut self, head: &HeadInfo, state: &HeadState) {
+        if !state.enabled {
+            self.disable_head(head);
+            return;
+        }
+
+        let head_configuration = self.enable_head(head);
+
+        if let Some(mode) = &state.mode {
+            match head.find_mode(mode.width, mode.height, mode.rate) {
+                Some(head_mode) => head_configuration.set_mode(head_mode),
+                None => head_configuration.set_custom_mode(mode),
+            };
+        }
+        if let Some((x, y)) = state.position {
+            head_configuration.set_position(x, y);
+        }
+        if let Some(scale) = state.scale {
+            head_configuration.set_scale(scale);
+        }
+        if let Some(transform) = state.transform {
+            head_configuration.set_transform(transform);
+        }
+        if let Some(adaptive_sync) = state.adaptive_sync {
+            head_configuration.set_adaptive_sync(adaptive_sync);
+        }
+    }
+
+    /// The same changes for the heads found by `resolve`, eg. the new objects of displays that were
+    /// reconnected. The advertised modes are looked up again on the new heads.
+    pub fn resolve_heads(
+        &self,
+        resolve: impl Fn(&HeadInfo) -> Option<HeadInfo>,
+    ) -> Result<Configuration, String> {
+        let heads = self
+            .heads
+            .iter()
+            .map(|head_configuration| {
+                let head = resolve(&head_configuration.head).ok_or(format!(
+                    "Display \"{}\" is no longer connected",
+                    head_configuration.head.name.clone().unwrap_or_default()
+                ))?;
+
+                let mode = head_configuration
+                    .mode
+                    .as_ref()
+                    .map(|setting| match setting {
+                        ModeSetting::Advertised(mode) => {
+                            match head.find_mode(mode.width, mode.height, mode.rate) {
+                                Some(head_mode) => ModeSetting::Advertised(head_mode.clone()),
+                                None => ModeSetting::Custom(setting.to_input()),
+                            }
+                        }
+                        ModeSetting::Custom(_) => setting.clone(),
+                    });
+
+                Ok(HeadConfiguration {
+                    head,
+                    mode,
+                    ..head_configuration.clone()
+                })
+            })
+            .collect::<Result<_, String>>()?;
+
+        Ok(Configuration { heads })
+    }
+
+    /// Adds the changes of another configuration, which win over the ones already requested.
+    pub fn merge(&mut self, other: &Configuration) {
+        for other_head in &other.heads {
+            if !other_head.enabled {
+                self.disable_head(&other_head.head);
+                continue;
+            }
+
+            let head_configuration = self.enable_head(&other_head.head);
+            if let Some(mode) = &other_head.mode {
+                head_configuration.mode = Some(mode.clone());
+            }
+            if let Some((x, y)) = other_head.position {
+                head_configuration.set_position(x, y);
+            }
+            if let Some(scale) = other_head.scale {
+                head_configuration.set_scale(scale);
+            }
+            if let Some(transform) = other_head.transform {
+                head_configuration.set_transform(transform);
+            }
+            if let Some(adaptive_sync) = other_head.adaptive_sync {
+                head_configuration.set_adaptive_sync(adaptive_sync);
+            }
+        }
+    }
+
+    /// Adds the heads left out with their current state, the compositor expects every head in a configuration.
+    pub fn include_unchanged(&mut self, heads: &[HeadInfo]) {
+        for head in heads {
+            if self.get(head).is_none() {
+                self.heads.push(HeadConfiguration::new(head, head.enabled));
+            }
+        }
+    }
+
+    /// Moves the layout so that the top left corner of its bounding box is at the origin, or the anchor
+    /// display when given.
+    ///
+    /// Returns an error when the anchor is not part of the configuration or ends up turned off.
+    pub fn normalize(&mut self, anchor: Option<&str>) -> Result<(), String> {
+        let offset = match anchor {
+            Some(anchor) => {
+                let anchor_configuration = self
+                    .heads
+                    .iter()
+                    .find(|configured| configured.head.name.as_deref() == Some(anchor))
+                    .ok_or(format!("Display \"{}\" not found", anchor))?;
+                let rect = anchor_configuration.resulting_rect().ok_or(format!(
+                    "The anchor display {} is off, it cannot be placed at the origin",
+                    anchor
+                ))?;
+
+                (rect.x, rect.y)
+            }
+            None => {
+                let rects: Vec<Rect> = self
+                    .heads
+                    .iter()
+                    .filter_map(HeadConfiguration::resulting_rect)
+                    .collect();
+
+                match (
+                    rects.iter().map(|rect| rect.x).min(),
+                    rects.iter().map(|rect| rect.y).min(),
+                ) {
+                    (Some(x), Some(y)) => (x, y),
+                    _ => return Ok(()),
+                }
+            }
+        };
+
+        if offset == (0, 0) {
+            return Ok(());
+        }
+
+        for head_configuration in self.heads.iter_mut() {
+            if let Some(rect) = head_configuration.resulting_rect() {
+                head_configuration.set_position(rect.x - offset.0, rect.y - offset.1);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn index_of(&mut self, head: &HeadInfo) -> usize {
+        match self
+            .heads
+            .iter()
+            .position(|configured| configured.head.head == head.head)
+        {
+            Some(index) => index,
+            None => {
+                self.heads.push(HeadConfiguration::new(head, true));
+                self.heads.len() - 1
+            }
+        }
+    }
+}
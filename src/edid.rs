@@ -0,0 +1,463 @@
+use crate::model::HeadInfo;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub static DEFAULT_SYSFS_ROOT: &str = "/sys";
+
+const EDID_HEADER: [u8; 8] = [0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00];
+const BLOCK_SIZE: usize = 128;
+const DESCRIPTOR_OFFSETS: [usize; 4] = [54, 72, 90, 108];
+
+const DESCRIPTOR_SERIAL: u8 = 0xFF;
+const DESCRIPTOR_NAME: u8 = 0xFC;
+const DESCRIPTOR_RANGE_LIMITS: u8 = 0xFD;
+
+const CTA_EXTENSION_TAG: u8 = 0x02;
+const CTA_EXTENDED_BLOCK_TAG: u8 = 7;
+const CTA_HDR_STATIC_METADATA_TAG: u8 = 6;
+
+/// Most common PNP IDs, the full registry is maintained by UEFI: https://uefi.org/PNP_ID_List
+static PNP_IDS: &[(&str, &str)] = &[
+    ("AAC", "AcerView"),
+    ("ACI", "ASUS"),
+    ("ACR", "Acer"),
+    ("AOC", "AOC"),
+    ("APP", "Apple"),
+    ("AUO", "AU Optronics"),
+    ("AUS", "ASUS"),
+    ("BNQ", "BenQ"),
+    ("BOE", "BOE"),
+    ("CMN", "Chimei Innolux"),
+    ("CMO", "Chi Mei Optoelectronics"),
+    ("DEL", "Dell"),
+    ("DLL", "Dell"),
+    ("EIZ", "EIZO"),
+    ("ENC", "EIZO"),
+    ("FUS", "Fujitsu Siemens"),
+    ("GBT", "Gigabyte"),
+    ("GSM", "LG Electronics"),
+    ("HKC", "HKC"),
+    ("HPN", "HP"),
+    ("HSD", "HannStar"),
+    ("HWP", "HP"),
+    ("IVM", "Iiyama"),
+    ("LEN", "Lenovo"),
+    ("LGD", "LG Display"),
+    ("LPL", "LG Philips"),
+    ("MAX", "Maxdata"),
+    ("MEI", "Panasonic"),
+    ("MSI", "MSI"),
+    ("MST", "MStar"),
+    ("NCP", "Nreal"),
+    ("NEC", "NEC"),
+    ("PHL", "Philips"),
+    ("RHT", "Red Hat"),
+    ("SAM", "Samsung"),
+    ("SDC", "Samsung Display"),
+    ("SEC", "Seiko Epson"),
+    ("SHP", "Sharp"),
+    ("SNY", "Sony"),
+    ("TOS", "Toshiba"),
+    ("TSB", "Toshiba"),
+    ("VIZ", "Vizio"),
+    ("VSC", "ViewSonic"),
+    ("XMI", "Xiaomi"),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DetailedTiming {
+    pub pixel_clock_khz: u32,
+    pub width: u32,
+    pub height: u32,
+    pub h_total: u32,
+    pub v_total: u32,
+    pub interlaced: bool,
+}
+
+impl DetailedTiming {
+    pub fn refresh_rate(&self) -> f64 {
+        let rate =
+            self.pixel_clock_khz as f64 * 1000.0 / (self.h_total as f64 * self.v_total as f64);
+        if self.interlaced { rate * 2.0 } else { rate }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RangeLimits {
+    pub min_vertical_rate: u32,
+    pub max_vertical_rate: u32,
+    pub min_horizontal_rate_khz: u32,
+    pub max_horizontal_rate_khz: u32,
+    pub max_pixel_clock_mhz: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EdidInfo {
+    pub manufacturer_id: String,
+    pub product_code: u16,
+    pub serial_number: Option<u32>,
+    pub manufacture_week: Option<u8>,
+    pub manufacture_year: u32,
+    /// The year is the model year rather than the manufacture year.
+    pub is_model_year: bool,
+    pub monitor_name: Option<String>,
+    pub serial_string: Option<String>,
+    pub native_timing: Option<DetailedTiming>,
+    pub range_limits: Option<RangeLimits>,
+    /// Transfer functions advertised in the CTA HDR static metadata block, empty for SDR only displays.
+    pub hdr_eotfs: Vec<&'static str>,
+}
+
+impl EdidInfo {
+    pub fn manufacturer_name(&self) -> Option<&'static str> {
+        PNP_IDS
+            .iter()
+            .find(|(id, _)| *id == self.manufacturer_id)
+            .map(|(_, name)| *name)
+    }
+
+    pub fn supports_hdr(&self) -> bool {
+        !self.hdr_eotfs.is_empty()
+    }
+}
+
+/// DRM exposes connectors as `card<N>-<connector>`, the connector being the head name (eg. `card1-DP-1`).
+pub fn find_edid_path(sysfs_root: &Path, connector: &str) -> Option<PathBuf> {
+    let drm_dir = sysfs_root.join("class/drm");
+
+    fs::read_dir(drm_dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .find(|entry| {
+            let file_name = entry.file_name();
+            let file_name = file_name.to_string_lossy();
+
+            file_name
+                .strip_prefix("card")
+                .and_then(|rest| rest.split_once('-'))
+                .is_some_and(|(card, name)| {
+                    card.chars().all(|c| c.is_ascii_digit()) && name == connector
+                })
+        })
+        .map(|entry| entry.path().join("edid"))
+        .filter(|path| path.is_file())
+}
+
+pub fn read_edid(sysfs_root: &Path, connector: &str) -> Option<EdidInfo> {
+    let bytes = fs::read(find_edid_path(sysfs_root, connector)?).ok()?;
+
+    // Disconnected connectors have an empty edid file
+    if bytes.is_empty() {
+        return None;
+    }
+
+    match parse_edid(&bytes) {
+        Ok(edid) => Some(edid),
+        Err(error) => {
+            eprintln!(
+                "Unable to read the EDID of display {}: {}",
+                connector, error
+            );
+            None
+        }
+    }
+}
+
+/// Fills the make, model and serial that the compositor did not advertise.
+pub fn fill_missing_identity(head: &mut HeadInfo, edid: &EdidInfo) {
+    let is_missing = |value: &Option<String>| value.as_deref().is_none_or(str::is_empty);
+
+    if is_missing(&head.make) {
+        head.make = Some(
+            edid.manufacturer_name()
+                .map(String::from)
+                .unwrap_or_else(|| edid.manufacturer_id.clone()),
+        );
+    }
+    if is_missing(&head.model) {
+        head.model = edid
+            .monitor_name
+            .clone()
+            .or_else(|| Some(format!("0x{:04X}", edid.product_code)));
+    }
+    if is_missing(&head.serial) {
+        head.serial = edid
+            .serial_string
+            .clone()
+            .or_else(|| edid.serial_number.map(|serial| serial.to_string()));
+    }
+}
+
+pub fn parse_edid(bytes: &[u8]) -> Result<EdidInfo, String> {
+    if bytes.len() < BLOCK_SIZE || bytes[0..8] != EDID_HEADER {
+        return Err(String::from("missing EDID header"));
+    }
+
+    let base = &bytes[0..BLOCK_SIZE];
+    if !has_valid_checksum(base) {
+        return Err(String::from("invalid checksum"));
+    }
+
+    let id = u16::from_be_bytes([base[8], base[9]]);
+    let manufacturer_id: String = [10, 5, 0]
+        .iter()
+        .map(|shift| (b'A' - 1 + ((id >> shift) & 0x1F) as u8) as char)
+        .collect();
+
+    let serial_number = u32::from_le_bytes([base[12], base[13], base[14], base[15]]);
+
+    let mut edid = EdidInfo {
+        manufacturer_id,
+        product_code: u16::from_le_bytes([base[10], base[11]]),
+        serial_number: (serial_number != 0).then_some(serial_number),
+        manufacture_week: (1..=54).contains(&base[16]).then_some(base[16]),
+        manufacture_year: 1990 + base[17] as u32,
+        is_model_year: base[16] == 0xFF,
+        monitor_name: None,
+        serial_string: None,
+        native_timing: None,
+        range_limits: None,
+        hdr_eotfs: vec![],
+    };
+
+    for offset in DESCRIPTOR_OFFSETS {
+        let descriptor = &base[offset..offset + 18];
+
+        if descriptor[0] != 0 || descriptor[1] != 0 {
+            // The first detailed timing is the preferred, native one
+            if edid.native_timing.is_none() {
+                edid.native_timing = Some(parse_detailed_timing(descriptor));
+            }
+            continue;
+        }
+
+        match descriptor[3] {
+            DESCRIPTOR_NAME => edid.monitor_name = parse_descriptor_text(descriptor),
+            DESCRIPTOR_SERIAL => edid.serial_string = parse_descriptor_text(descriptor),
+            DESCRIPTOR_RANGE_LIMITS => edid.range_limits = Some(parse_range_limits(descriptor)),
+            _ => {}
+        }
+    }
+
+    let extension_count = base[126] as usize;
+    for block in bytes[BLOCK_SIZE..]
+        .chunks_exact(BLOCK_SIZE)
+        .take(extension_count)
+        .filter(|block| block[0] == CTA_EXTENSION_TAG && has_valid_checksum(block))
+    {
+        edid.hdr_eotfs.extend(parse_cta_hdr_eotfs(block));
+    }
+
+    Ok(edid)
+}
+
+fn has_valid_checksum(block: &[u8]) -> bool {
+    block.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) == 0
+}
+
+fn parse_detailed_timing(descriptor: &[u8]) -> DetailedTiming {
+    let width = descriptor[2] as u32 | ((descriptor[4] as u32 & 0xF0) << 4);
+    let h_blank = descriptor[3] as u32 | ((descriptor[4] as u32 & 0x0F) << 8);
+    let height = descriptor[5] as u32 | ((descriptor[7] as u32 & 0xF0) << 4);
+    let v_blank = descriptor[6] as u32 | ((descriptor[7] as u32 & 0x0F) << 8);
+
+    DetailedTiming {
+        pixel_clock_khz: u16::from_le_bytes([descriptor[0], descriptor[1]]) as u32 * 10,
+        width,
+        height,
+        h_total: width + h_blank,
+        v_total: height + v_blank,
+        interlaced: descriptor[17] & 0x80 != 0,
+    }
+}
+
+fn parse_descriptor_text(descriptor: &[u8]) -> Option<String> {
+    let text: String = descriptor[5..18]
+        .iter()
+        .take_while(|byte| **byte != b'\n')
+        .map(|byte| *byte as char)
+        .collect();
+    let text = text.trim();
+
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+fn parse_range_limits(descriptor: &[u8]) -> RangeLimits {
+    // EDID 1.4 offsets for rates above 255
+    let flags = descriptor[4];
+    let vertical_min_offset = if flags & 0x03 == 0x03 { 255 } else { 0 };
+    let vertical_max_offset = if flags & 0x02 != 0 { 255 } else { 0 };
+    let horizontal_min_offset = if flags & 0x0C == 0x0C { 255 } else { 0 };
+    let horizontal_max_offset = if flags & 0x08 != 0 { 255 } else { 0 };
+
+    RangeLimits {
+        min_vertical_rate: descriptor[5] as u32 + vertical_min_offset,
+        max_vertical_rate: descriptor[6] as u32 + vertical_max_offset,
+        min_horizontal_rate_khz: descriptor[7] as u32 + horizontal_min_offset,
+        max_horizontal_rate_khz: descriptor[8] as u32 + horizontal_max_offset,
+        max_pixel_clock_mhz: (descriptor[9] != 0).then_some(descriptor[9] as u32 * 10),
+    }
+}
+
+/// Reads the supported transfer functions out of the HDR static metadata data block of a CTA-861 extension.
+fn parse_cta_hdr_eotfs(block: &[u8]) -> Vec<&'static str> {
+    let data_blocks_end = (block[2] as usize).clamp(4, BLOCK_SIZE - 1);
+    let mut offset = 4;
+
+    while offset < data_blocks_end {
+        let tag = block[offset] >> 5;
+        let length = (block[offset] & 0x1F) as usize;
+
+        if tag == CTA_EXTENDED_BLOCK_TAG
+            && length >= 2
+            && offset + 2 < data_blocks_end
+            && block[offset + 1] == CTA_HDR_STATIC_METADATA_TAG
+        {
+            let eotfs = block[offset + 2];
+            return [(1, "Traditional HDR"), (2, "PQ"), (3, "HLG")]
+                .iter()
+                .filter(|(bit, _)| eotfs & (1 << bit) != 0)
+                .map(|(_, name)| *name)
+                .collect();
+        }
+
+        offset += length + 1;
+    }
+
+    vec![]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static DELL_U2415: &[u8] = include_bytes!("../tests/fixtures/edid/dell-u2415.bin");
+    static ASUS_PG259QN: &[u8] = include_bytes!("../tests/fixtures/edid/asus-pg259qn.bin");
+    static SAMSUNG_TV: &[u8] = include_bytes!("../tests/fixtures/edid/samsung-tv.bin");
+
+    fn sysfs_root() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/sysfs")
+    }
+
+    #[test]
+    fn identity_of_a_desktop_monitor() {
+        let edid = parse_edid(DELL_U2415).unwrap();
+
+        assert_eq!(edid.manufacturer_id, "DEL");
+        assert_eq!(edid.manufacturer_name(), Some("Dell"));
+        assert_eq!(edid.product_code, 0xA0BA);
+        assert_eq!(edid.monitor_name.as_deref(), Some("DELL U2415"));
+        assert_eq!(edid.serial_string.as_deref(), Some("7MT0176J0ABL"));
+        assert_eq!(edid.serial_number, Some(0x304C4C41));
+    }
+
+    #[test]
+    fn manufacture_week_and_year() {
+        let dell = parse_edid(DELL_U2415).unwrap();
+        assert_eq!(dell.manufacture_week, Some(12));
+        assert_eq!(dell.manufacture_year, 2017);
+        assert!(!dell.is_model_year);
+
+        let asus = parse_edid(ASUS_PG259QN).unwrap();
+        assert_eq!(asus.manufacture_week, None);
+        assert_eq!(asus.manufacture_year, 2021);
+        assert!(asus.is_model_year);
+
+        // Week 0 means the week is not specified
+        let samsung = parse_edid(SAMSUNG_TV).unwrap();
+        assert_eq!(samsung.manufacture_week, None);
+        assert_eq!(samsung.manufacture_year, 2019);
+        assert!(!samsung.is_model_year);
+    }
+
+    #[test]
+    fn native_mode_is_the_first_detailed_timing() {
+        let dell = parse_edid(DELL_U2415).unwrap().native_timing.unwrap();
+        assert_eq!((dell.width, dell.height), (1920, 1200));
+        assert_eq!(dell.pixel_clock_khz, 154000);
+        assert_eq!((dell.h_total, dell.v_total), (2080, 1235));
+        assert!((dell.refresh_rate() - 59.95).abs() < 0.01);
+
+        let samsung = parse_edid(SAMSUNG_TV).unwrap().native_timing.unwrap();
+        assert_eq!((samsung.width, samsung.height), (1920, 1080));
+        assert!((samsung.refresh_rate() - 60.0).abs() < 0.01);
+        assert!(!samsung.interlaced);
+    }
+
+    #[test]
+    fn range_limits_without_offsets() {
+        let limits = parse_edid(DELL_U2415).unwrap().range_limits.unwrap();
+
+        assert_eq!(
+            limits,
+            RangeLimits {
+                min_vertical_rate: 56,
+                max_vertical_rate: 76,
+                min_horizontal_rate_khz: 30,
+                max_horizontal_rate_khz: 83,
+                max_pixel_clock_mhz: Some(170),
+            }
+        );
+    }
+
+    #[test]
+    fn range_limits_with_the_edid_1_4_maximum_offsets() {
+        let limits = parse_edid(ASUS_PG259QN).unwrap().range_limits.unwrap();
+
+        assert_eq!(limits.min_vertical_rate, 48);
+        assert_eq!(limits.max_vertical_rate, 360);
+        assert_eq!(limits.min_horizontal_rate_khz, 30);
+        assert_eq!(limits.max_horizontal_rate_khz, 420);
+        assert_eq!(limits.max_pixel_clock_mhz, Some(600));
+    }
+
+    #[test]
+    fn range_limits_with_the_edid_1_4_minimum_offsets() {
+        let mut descriptor = [0u8; 18];
+        descriptor[3] = DESCRIPTOR_RANGE_LIMITS;
+        descriptor[4] = 0x0F;
+        descriptor[5..9].copy_from_slice(&[5, 45, 10, 200]);
+
+        let limits = parse_range_limits(&descriptor);
+        assert_eq!(limits.min_vertical_rate, 260);
+        assert_eq!(limits.max_vertical_rate, 300);
+        assert_eq!(limits.min_horizontal_rate_khz, 265);
+        assert_eq!(limits.max_horizontal_rate_khz, 455);
+        assert_eq!(limits.max_pixel_clock_mhz, None);
+    }
+
+    #[test]
+    fn hdr_transfer_functions_of_the_cta_extension() {
+        assert!(!parse_edid(DELL_U2415).unwrap().supports_hdr());
+        assert_eq!(parse_edid(ASUS_PG259QN).unwrap().hdr_eotfs, vec!["PQ"]);
+        // The traditional SDR bit is set as well and is not reported
+        assert_eq!(parse_edid(SAMSUNG_TV).unwrap().hdr_eotfs, vec!["PQ", "HLG"]);
+    }
+
+    #[test]
+    fn invalid_blobs_are_rejected() {
+        assert!(parse_edid(&DELL_U2415[..100]).is_err());
+
+        let mut corrupted = DELL_U2415.to_vec();
+        corrupted[20] ^= 0xFF;
+        assert_eq!(
+            parse_edid(&corrupted),
+            Err(String::from("invalid checksum"))
+        );
+    }
+
+    #[test]
+    fn edid_read_from_sysfs_by_connector() {
+        let root = sysfs_root();
+
+        let edid = read_edid(&root, "DP-1").unwrap();
+        assert_eq!(edid.monitor_name.as_deref(), Some("PG259QN"));
+        let edid = read_edid(&root, "HDMI-A-1").unwrap();
+        assert_eq!(edid.monitor_name.as_deref(), Some("SAMSUNG"));
+
+        // Disconnected connectors have an empty edid, unknown ones no directory at all
+        assert_eq!(read_edid(&root, "DP-2"), None);
+        assert_eq!(read_edid(&root, "eDP-1"), None);
+        assert_eq!(read_edid(&root, "1-DP-1"), None);
+    }
+}
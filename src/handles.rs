@@ -1,9 +1,16 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use wayland_client::protocol::wl_callback::{self, WlCallback};
 use wayland_client::protocol::wl_output::Transform;
 use wayland_client::protocol::wl_registry;
 use wayland_client::{Connection, Dispatch, Proxy, QueueHandle, event_created_child};
 use wayland_protocols_wlr::output_management::v1::client::zwlr_output_mode_v1::Event as OutputModeEvent;
 
 use crate::model::{AppData, ConfigResult, HeadInfo, HeadMode};
+use crate::trace::{
+    self, describe_callback_event, describe_configuration_event, describe_head_event,
+    describe_manager_event, describe_mode_event, describe_registry_event,
+};
 use wayland_protocols_wlr::output_management::v1::client::zwlr_output_configuration_head_v1::ZwlrOutputConfigurationHeadV1;
 use wayland_protocols_wlr::output_management::v1::client::zwlr_output_configuration_v1::{
     Event as ConfigurationEvent, ZwlrOutputConfigurationV1,
@@ -14,9 +21,14 @@ use wayland_protocols_wlr::output_management::v1::client::zwlr_output_head_v1::{
 use wayland_protocols_wlr::output_management::v1::client::zwlr_output_manager_v1::{
     self, Event as ManagerEvent, ZwlrOutputManagerV1,
 };
-use wayland_protocols_wlr::output_management::v1::client::zwlr_output_mode_v1::ZwlrOutputModeV1;
+use wayland_protocols_wlr::output_management::v1::client::zwlr_output_mode_v1::{
+    self, ZwlrOutputModeV1,
+};
 
 pub static OUTPUT_MANAGER_INTERFACE_NAME: &str = "zwlr_output_manager_v1";
+/// Newest version of the output manager protocol wlout knows about, older ones are bound when that is
+/// all the compositor offers.
+pub const OUTPUT_MANAGER_VERSION: u32 = 4;
 
 /**
  * This method subscribes to the Global events. The global events advertise the capabilities of the system.
@@ -31,29 +43,56 @@ impl Dispatch<wl_registry::WlRegistry, ()> for AppData {
         _: &Connection,
         qh: &QueueHandle<AppData>,
     ) {
+        trace::event(&registry.id(), || describe_registry_event(&event));
+
         if let wl_registry::Event::Global {
             name,
             interface,
-            version: _,
+            version,
         } = event
         {
+            state.globals.push((interface.clone(), version));
+
             if interface == OUTPUT_MANAGER_INTERFACE_NAME {
+                let version = version.min(OUTPUT_MANAGER_VERSION);
+
                 state.output_manager_found = true;
-                registry.bind::<ZwlrOutputManagerV1, _, _>(name, 4, qh, ());
+                state.manager_version = version;
+                registry.bind::<ZwlrOutputManagerV1, _, _>(name, version, qh, ());
             }
         }
     }
 }
 
+/// Answer to a sync request, sent once the compositor processed every request sent before it.
+impl Dispatch<WlCallback, Arc<AtomicBool>> for AppData {
+    fn event(
+        _state: &mut Self,
+        callback: &WlCallback,
+        event: wl_callback::Event,
+        done: &Arc<AtomicBool>,
+        _: &Connection,
+        _: &QueueHandle<AppData>,
+    ) {
+        trace::event(&callback.id(), || describe_callback_event(&event));
+
+        if let wl_callback::Event::Done { .. } = event {
+            done.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
 impl Dispatch<ZwlrOutputConfigurationV1, ()> for AppData {
     fn event(
         state: &mut Self,
-        _configuration: &ZwlrOutputConfigurationV1,
+        configuration: &ZwlrOutputConfigurationV1,
         event: ConfigurationEvent,
         _: &(),
         _: &Connection,
         _: &QueueHandle<AppData>,
     ) {
+        trace::event(&configuration.id(), || describe_configuration_event(&event));
+
         match event {
             ConfigurationEvent::Succeeded => state.config_result = Some(ConfigResult::Succeeded),
             ConfigurationEvent::Failed => state.config_result = Some(ConfigResult::Failed),
@@ -72,8 +111,12 @@ impl Dispatch<ZwlrOutputManagerV1, ()> for AppData {
         _: &Connection,
         _: &QueueHandle<AppData>,
     ) {
+        trace::event(&manager.id(), || describe_manager_event(&event));
+
         state.manager = Some(manager.clone());
         match event {
+            // Sent once stopped, the compositor destroys the manager right after
+            ManagerEvent::Finished => state.manager = None,
             ManagerEvent::Head { head } => {
                 state.heads.insert(head.id(), HeadInfo::new(head.clone()));
             }
@@ -99,6 +142,18 @@ impl Dispatch<ZwlrOutputHeadV1, ()> for AppData {
         _: &Connection,
         _: &QueueHandle<AppData>,
     ) {
+        trace::event(&head.id(), || describe_head_event(&event));
+
+        // The display was unplugged, it is no longer part of the layout
+        if let HeadEvent::Finished = event {
+            state.heads.remove(&head.id());
+            if state.supports(zwlr_output_head_v1::REQ_RELEASE_SINCE) {
+                head.release();
+                trace::request(&head.id(), || String::from("release()"));
+            }
+            return;
+        }
+
         let current_head = state.heads.get_mut(&head.id()).unwrap();
 
         match event {
@@ -110,7 +165,10 @@ impl Dispatch<ZwlrOutputHeadV1, ()> for AppData {
                 current_head.position_y = Some(y)
             }
             HeadEvent::CurrentMode { mode } => {
-                current_head.modes.get_mut(&mode.id()).unwrap().is_current = true
+                // Sent again whenever the mode changes while connected
+                for head_mode in current_head.modes.values_mut() {
+                    head_mode.is_current = head_mode.mode.as_ref() == Some(&mode);
+                }
             }
             HeadEvent::Make { make } => current_head.make = Some(make),
             HeadEvent::Model { model } => current_head.model = Some(model),
@@ -140,6 +198,7 @@ impl Dispatch<ZwlrOutputHeadV1, ()> for AppData {
                         HeadMode {
                             mode: Some(mode.clone()),
                             rate: 0,
+                            refresh: 0,
                             height: 0,
                             width: 0,
                             is_preferred: false,
@@ -166,6 +225,19 @@ impl Dispatch<ZwlrOutputModeV1, ()> for AppData {
         _: &Connection,
         _: &QueueHandle<AppData>,
     ) {
+        trace::event(&_mode.id(), || describe_mode_event(&event));
+
+        if let OutputModeEvent::Finished = event {
+            for head in state.heads.values_mut() {
+                head.modes.remove(&_mode.id());
+            }
+            if state.supports(zwlr_output_mode_v1::REQ_RELEASE_SINCE) {
+                _mode.release();
+                trace::request(&_mode.id(), || String::from("release()"));
+            }
+            return;
+        }
+
         for head in state.heads.values_mut() {
             match head.modes.get_mut(&_mode.id()) {
                 Some(res) => match event {
@@ -173,7 +245,10 @@ impl Dispatch<ZwlrOutputModeV1, ()> for AppData {
                         res.height = height;
                         res.width = width;
                     }
-                    OutputModeEvent::Refresh { refresh } => res.rate = refresh as i32 / 1000,
+                    OutputModeEvent::Refresh { refresh } => {
+                        res.refresh = refresh;
+                        res.rate = refresh / 1000;
+                    }
                     OutputModeEvent::Preferred {} => res.is_preferred = true,
                     _ => {}
                 },
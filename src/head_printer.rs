@@ -1,11 +1,15 @@
-use crate::model::HeadInfo;
+use crate::edid::EdidInfo;
+use crate::history::HistoryEntry;
+use crate::model::{HeadInfo, PropertyChange};
+use crate::timing::{Blanking, ModeTiming};
 use std::io::Write;
 use tabwriter::TabWriter;
 
 pub fn print_heads_detail(heads: Vec<HeadInfo>) {
     let mut tw = TabWriter::new(vec![]);
-    let mut string_result =
-        String::from("Name\tEnabled\tCurrent Mode\tMake\tModel\tPhysical Size\tPosition");
+    let mut string_result = String::from(
+        "Name\tEnabled\tCurrent Mode\tMake\tModel\tPhysical Size\tPosition\tDiagonal\tPPI\tLogical DPI\tAspect Ratio",
+    );
 
     for head in heads {
         let phys_size_str;
@@ -41,15 +45,230 @@ pub fn print_heads_detail(heads: Vec<HeadInfo>) {
             current_mode_str = format!("{}", mode.unwrap())
         }
 
+        let not_available = || String::from("N/A");
+
         string_result += format!(
-            "\n{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            "\n{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
             head.name.clone().unwrap(),
             head.enabled,
             current_mode_str,
             head.make.clone().or(Some(String::from("N/A"))).unwrap(),
             head.model.clone().or(Some(String::from("N/A"))).unwrap(),
             phys_size_str,
-            position_str
+            position_str,
+            head.diagonal_inches()
+                .map_or_else(not_available, |diagonal| format!("{:.1}\"", diagonal)),
+            head.ppi()
+                .map_or_else(not_available, |ppi| format!("{:.0}", ppi)),
+            head.logical_dpi()
+                .map_or_else(not_available, |dpi| format!("{:.0}", dpi)),
+            head.aspect_ratio().unwrap_or_else(not_available)
+        )
+        .as_str()
+    }
+
+    write!(&mut tw, "{}", string_result).unwrap();
+
+    tw.flush().unwrap();
+    let written = String::from_utf8(tw.into_inner().unwrap()).unwrap();
+    println!("{}", written);
+}
+
+pub fn print_mode_timings(timings: Vec<ModeTiming>) {
+    let mut tw = TabWriter::new(vec![]);
+    let mut string_result =
+        String::from("Timing\tPixel Clock\tHorizontal Rate\tRefresh Rate\tTotal Size");
+
+    for timing in &timings {
+        let name = match timing.blanking {
+            Blanking::Standard => "CVT",
+            Blanking::Reduced => "CVT-RB",
+        };
+
+        string_result += format!(
+            "\n{}\t{:.2} MHz\t{:.2} kHz\t{:.2} Hz\t{}x{}",
+            name,
+            timing.pixel_clock_mhz(),
+            timing.horizontal_frequency_khz(),
+            timing.refresh_rate(),
+            timing.h_total,
+            timing.v_total
+        )
+        .as_str()
+    }
+
+    write!(&mut tw, "{}", string_result).unwrap();
+
+    tw.flush().unwrap();
+    let written = String::from_utf8(tw.into_inner().unwrap()).unwrap();
+    println!("{}\n", written);
+
+    for timing in &timings {
+        println!("{}", timing.modeline());
+    }
+}
+
+pub fn print_edid_detail(edids: Vec<(String, EdidInfo)>) {
+    let mut tw = TabWriter::new(vec![]);
+    let mut string_result =
+        String::from("Name\tManufacturer\tManufactured\tNative Timing\tRefresh Range\tHDR");
+
+    for (name, edid) in edids {
+        let manufacturer_str = match edid.manufacturer_name() {
+            Some(manufacturer) => format!("{} ({})", manufacturer, edid.manufacturer_id),
+            None => edid.manufacturer_id.clone(),
+        };
+
+        let manufactured_str = match (edid.is_model_year, edid.manufacture_week) {
+            (true, _) => format!("model year {}", edid.manufacture_year),
+            (false, Some(week)) => format!("week {} {}", week, edid.manufacture_year),
+            (false, None) => edid.manufacture_year.to_string(),
+        };
+
+        let native_timing_str = edid.native_timing.as_ref().map_or_else(
+            || String::from("N/A"),
+            |timing| {
+                format!(
+                    "{}x{}@{:.2}",
+                    timing.width,
+                    timing.height,
+                    timing.refresh_rate()
+                )
+            },
+        );
+
+        let refresh_range_str = edid.range_limits.as_ref().map_or_else(
+            || String::from("N/A"),
+            |limits| {
+                format!(
+                    "{}-{} Hz",
+                    limits.min_vertical_rate, limits.max_vertical_rate
+                )
+            },
+        );
+
+        let hdr_str = if edid.supports_hdr() {
+            edid.hdr_eotfs.join(", ")
+        } else {
+            String::from("No")
+        };
+
+        string_result += format!(
+            "\n{}\t{}\t{}\t{}\t{}\t{}",
+            name, manufacturer_str, manufactured_str, native_timing_str, refresh_range_str, hdr_str
+        )
+        .as_str()
+    }
+
+    write!(&mut tw, "{}", string_result).unwrap();
+
+    tw.flush().unwrap();
+    let written = String::from_utf8(tw.into_inner().unwrap()).unwrap();
+    println!("{}", written);
+}
+
+/// Prints the most recent entry first, numbered the way `wlout undo` expects.
+pub fn print_history(entries: Vec<HistoryEntry>) {
+    let mut tw = TabWriter::new(vec![]);
+    let mut string_result = String::from("#\tDate (UTC)\tDisplays\tCommand");
+
+    for (i, entry) in entries.iter().rev().enumerate() {
+        string_result += format!(
+            "\n{}\t{}\t{}\t{}",
+            i + 1,
+            entry.formatted_timestamp(),
+            entry.display_names().join(", "),
+            entry.command
+        )
+        .as_str()
+    }
+
+    write!(&mut tw, "{}", string_result).unwrap();
+
+    tw.flush().unwrap();
+    let written = String::from_utf8(tw.into_inner().unwrap()).unwrap();
+    println!("{}", written);
+}
+
+/// Prints the properties that change, the display name only on its first line.
+pub fn print_changes(changes: Vec<(String, Vec<PropertyChange>)>) {
+    let mut tw = TabWriter::new(vec![]);
+    let mut string_result = String::from("Display\tProperty\tBefore\tAfter");
+
+    for (name, head_changes) in &changes {
+        for (i, change) in head_changes.iter().enumerate() {
+            string_result += format!(
+                "\n{}\t{}\t{}\t{}",
+                if i == 0 { name.as_str() } else { "" },
+                change.property,
+                change.before,
+                change.after
+            )
+            .as_str()
+        }
+    }
+
+    write!(&mut tw, "{}", string_result).unwrap();
+
+    tw.flush().unwrap();
+    let written = String::from_utf8(tw.into_inner().unwrap()).unwrap();
+    println!("{}", written);
+}
+
+/// Prints the values accepted and rejected by the compositor for each capability of a display.
+pub fn print_capabilities(capabilities: Vec<(String, Vec<String>, Vec<String>)>) {
+    let mut tw = TabWriter::new(vec![]);
+    let mut string_result = String::from("Capability\tAccepted\tRejected");
+
+    for (capability, accepted, rejected) in &capabilities {
+        let list = |values: &Vec<String>| match values.is_empty() {
+            true => String::from("none"),
+            false => values.join(", "),
+        };
+
+        string_result +=
+            format!("\n{}\t{}\t{}", capability, list(accepted), list(rejected)).as_str()
+    }
+
+    write!(&mut tw, "{}", string_result).unwrap();
+
+    tw.flush().unwrap();
+    let written = String::from_utf8(tw.into_inner().unwrap()).unwrap();
+    println!("{}", written);
+}
+
+pub fn print_globals(globals: &[(String, u32)]) {
+    let mut tw = TabWriter::new(vec![]);
+    let mut string_result = String::from("Interface\tVersion");
+
+    for (interface, version) in globals {
+        string_result += format!("\n{}\t{}", interface, version).as_str()
+    }
+
+    write!(&mut tw, "{}", string_result).unwrap();
+
+    tw.flush().unwrap();
+    let written = String::from_utf8(tw.into_inner().unwrap()).unwrap();
+    println!("{}", written);
+}
+
+/// Prints what each display reports to identify itself, `missing` when the compositor does not send it.
+pub fn print_identification(heads: &[HeadInfo]) {
+    let mut tw = TabWriter::new(vec![]);
+    let mut string_result = String::from("Name\tMake\tModel\tSerial");
+
+    for head in heads {
+        let value = |value: &Option<String>| match value {
+            Some(value) if !value.is_empty() => value.clone(),
+            _ => String::from("missing"),
+        };
+
+        string_result += format!(
+            "\n{}\t{}\t{}\t{}",
+            value(&head.name),
+            value(&head.make),
+            value(&head.model),
+            value(&head.serial)
         )
         .as_str()
     }
@@ -60,3 +279,52 @@ pub fn print_heads_detail(heads: Vec<HeadInfo>) {
     let written = String::from_utf8(tw.into_inner().unwrap()).unwrap();
     println!("{}", written);
 }
+
+/// Prints the display on one line of tab separated `key=value` pairs, like the saved states.
+/// The keys without a value, eg. `ppi` for a projector, are left out.
+pub fn print_head_parsable(head: &HeadInfo) {
+    let sanitize = |value: &str| value.replace(['\t', '\n'], " ");
+    let mut fields = vec![format!(
+        "name={}",
+        sanitize(head.name.as_deref().unwrap_or_default())
+    )];
+
+    let optional_fields = [
+        ("make", head.make.as_deref().map(sanitize)),
+        ("model", head.model.as_deref().map(sanitize)),
+        ("serial", head.serial.as_deref().map(sanitize)),
+        ("enabled", Some(head.enabled.to_string())),
+        ("mode", head.get_current_mode().map(|mode| mode.to_string())),
+        (
+            "position",
+            head.position_x
+                .zip(head.position_y)
+                .map(|(x, y)| format!("{},{}", x, y)),
+        ),
+        ("scale", head.scale.map(|scale| scale.to_string())),
+        (
+            "physical_size",
+            head.physical_width
+                .zip(head.physical_height)
+                .map(|(width, height)| format!("{}x{}", width, height)),
+        ),
+        (
+            "diagonal_inches",
+            head.diagonal_inches()
+                .map(|diagonal| format!("{:.1}", diagonal)),
+        ),
+        ("ppi", head.ppi().map(|ppi| format!("{:.1}", ppi))),
+        (
+            "logical_dpi",
+            head.logical_dpi().map(|dpi| format!("{:.1}", dpi)),
+        ),
+        ("aspect_ratio", head.aspect_ratio()),
+    ];
+    for (key, value) in optional_fields {
+        if let Some(value) = value {
+            fields.push(format!("{}={}", key, value));
+        }
+    }
+
+    println!("{}", fields.join("\t"));
+}
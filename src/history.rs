@@ -0,0 +1,182 @@
+use crate::model::HeadState;
+use crate::store::state_dir;
+use std::fs;
+use std::io;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Journal of the configurations applied by wlout, listed by `wlout history` and reverted by `wlout undo`.
+pub static HISTORY_FILE: &str = "history";
+
+/// The oldest entries are dropped once the journal grows past this size.
+const MAX_HISTORY_ENTRIES: usize = 100;
+
+const ENTRY_PREFIX: &str = "entry";
+const BEFORE_PREFIX: &str = "before";
+const AFTER_PREFIX: &str = "after";
+
+/// One applied configuration, with the state of the displays it changed.
+///
+/// It is written as an `entry` line holding the timestamp and command line, followed by one `before` and
+/// one `after` line per changed display.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    /// Seconds since the UNIX epoch.
+    pub timestamp: u64,
+    pub command: String,
+    pub before: Vec<HeadState>,
+    pub after: Vec<HeadState>,
+}
+
+impl HistoryEntry {
+    pub fn new(before: &[HeadState], after: &[HeadState]) -> Self {
+        let changed = |state: &HeadState, others: &[HeadState]| !others.contains(state);
+
+        HistoryEntry {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_secs())
+                .unwrap_or_default(),
+            command: std::iter::once(String::from("wlout"))
+                .chain(std::env::args().skip(1))
+                .collect::<Vec<String>>()
+                .join(" ")
+                .replace(['\t', '\n'], " "),
+            before: before
+                .iter()
+                .filter(|state| changed(state, after))
+                .cloned()
+                .collect(),
+            after: after
+                .iter()
+                .filter(|state| changed(state, before))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.before.is_empty() && self.after.is_empty()
+    }
+
+    /// Names of the displays changed by this entry.
+    pub fn display_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .before
+            .iter()
+            .chain(self.after.iter())
+            .map(|state| state.name.clone())
+            .collect();
+        names.sort();
+        names.dedup();
+
+        names
+    }
+
+    /// The timestamp as `YYYY-MM-DD HH:MM:SS` in UTC.
+    pub fn formatted_timestamp(&self) -> String {
+        let days = (self.timestamp / 86400) as i64;
+        let seconds = self.timestamp % 86400;
+
+        // Howard Hinnant's days to civil date algorithm
+        let z = days + 719468;
+        let era = z.div_euclid(146097);
+        let day_of_era = z.rem_euclid(146097);
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let mp = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * mp + 2) / 5 + 1;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 };
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+        format!(
+            "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+            year,
+            month,
+            day,
+            seconds / 3600,
+            seconds % 3600 / 60,
+            seconds % 60
+        )
+    }
+}
+
+/// The journal, oldest entry first.
+pub fn load_history() -> Vec<HistoryEntry> {
+    let path = state_dir().join(HISTORY_FILE);
+
+    let Ok(content) = fs::read_to_string(&path) else {
+        return vec![];
+    };
+
+    let mut entries: Vec<HistoryEntry> = vec![];
+    for line in content.lines().filter(|line| !line.trim().is_empty()) {
+        let (prefix, rest) = line.split_once('\t').unwrap_or((line, ""));
+
+        let parsed = match prefix {
+            ENTRY_PREFIX => parse_entry_line(rest).map(|entry| entries.push(entry)),
+            BEFORE_PREFIX | AFTER_PREFIX => match entries.last_mut() {
+                Some(entry) => rest.parse::<HeadState>().map(|state| {
+                    if prefix == BEFORE_PREFIX {
+                        entry.before.push(state)
+                    } else {
+                        entry.after.push(state)
+                    }
+                }),
+                None => Err(String::from("Display state outside of an entry")),
+            },
+            _ => Err(format!("Unknown line \"{}\"", prefix)),
+        };
+
+        if let Err(error) = parsed {
+            eprintln!(
+                "Ignoring invalid history line in {}: {}",
+                path.display(),
+                error
+            );
+        }
+    }
+
+    entries
+}
+
+fn parse_entry_line(line: &str) -> Result<HistoryEntry, String> {
+    let (timestamp, command) = line
+        .split_once('\t')
+        .ok_or(format!("Malformed entry \"{}\"", line))?;
+
+    Ok(HistoryEntry {
+        timestamp: timestamp
+            .parse()
+            .map_err(|_| format!("Malformed timestamp \"{}\"", timestamp))?,
+        command: command.to_string(),
+        before: vec![],
+        after: vec![],
+    })
+}
+
+pub fn save_history(entries: &[HistoryEntry]) -> io::Result<()> {
+    let dir = state_dir();
+    fs::create_dir_all(&dir)?;
+
+    let mut content = String::new();
+    for entry in entries {
+        content += &format!("{}\t{}\t{}\n", ENTRY_PREFIX, entry.timestamp, entry.command);
+        for state in &entry.before {
+            content += &format!("{}\t{}\n", BEFORE_PREFIX, state);
+        }
+        for state in &entry.after {
+            content += &format!("{}\t{}\n", AFTER_PREFIX, state);
+        }
+    }
+
+    fs::write(dir.join(HISTORY_FILE), content)
+}
+
+pub fn record_history_entry(entry: HistoryEntry) -> io::Result<()> {
+    let mut entries = load_history();
+    entries.push(entry);
+
+    let overflow = entries.len().saturating_sub(MAX_HISTORY_ENTRIES);
+    save_history(&entries[overflow..])
+}
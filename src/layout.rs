@@ -0,0 +1,164 @@
+/// A display in the global compositor space, sized in logical pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Rect {
+    pub fn right(&self) -> i32 {
+        self.x + self.width
+    }
+
+    pub fn bottom(&self) -> i32 {
+        self.y + self.height
+    }
+
+    fn overlaps_vertically(&self, other: &Rect) -> bool {
+        self.y < other.bottom() && other.y < self.bottom()
+    }
+
+    fn overlaps_horizontally(&self, other: &Rect) -> bool {
+        self.x < other.right() && other.x < self.right()
+    }
+}
+
+/// Computes where displays should go once some of them changed size (new mode, scale or transform),
+/// so that the displays that were touching on the right or below stay touching.
+///
+/// Displays that were not touching anything keep their position.
+pub fn rearrange_after_resize(before: &[Rect], new_sizes: &[(i32, i32)]) -> Vec<Rect> {
+    let mut after: Vec<Rect> = before
+        .iter()
+        .zip(new_sizes)
+        .map(|(rect, (width, height))| Rect {
+            width: *width,
+            height: *height,
+            ..*rect
+        })
+        .collect();
+
+    let mut by_x: Vec<usize> = (0..before.len()).collect();
+    by_x.sort_by_key(|i| before[*i].x);
+    for &i in &by_x {
+        if let Some(x) = (0..before.len())
+            .filter(|j| {
+                before[*j].right() == before[i].x && before[*j].overlaps_vertically(&before[i])
+            })
+            .map(|j| after[j].right())
+            .max()
+        {
+            after[i].x = x;
+        }
+    }
+
+    let mut by_y: Vec<usize> = (0..before.len()).collect();
+    by_y.sort_by_key(|i| before[*i].y);
+    for &i in &by_y {
+        if let Some(y) = (0..before.len())
+            .filter(|j| {
+                before[*j].bottom() == before[i].y && before[*j].overlaps_horizontally(&before[i])
+            })
+            .map(|j| after[j].bottom())
+            .max()
+        {
+            after[i].y = y;
+        }
+    }
+
+    after
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(x: i32, y: i32, width: i32, height: i32) -> Rect {
+        Rect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn display_on_the_right_follows_a_bigger_one() {
+        let before = [rect(0, 0, 1920, 1080), rect(1920, 0, 1920, 1080)];
+
+        let after = rearrange_after_resize(&before, &[(2560, 1440), (1920, 1080)]);
+
+        assert_eq!(after, [rect(0, 0, 2560, 1440), rect(2560, 0, 1920, 1080)]);
+    }
+
+    #[test]
+    fn display_on_the_right_follows_a_smaller_one() {
+        let before = [rect(0, 0, 2560, 1440), rect(2560, 0, 1920, 1080)];
+
+        let after = rearrange_after_resize(&before, &[(1280, 720), (1920, 1080)]);
+
+        assert_eq!(after, [rect(0, 0, 1280, 720), rect(1280, 0, 1920, 1080)]);
+    }
+
+    #[test]
+    fn chain_of_displays_stays_touching() {
+        let before = [
+            rect(0, 0, 1920, 1080),
+            rect(1920, 0, 1920, 1080),
+            rect(3840, 0, 1920, 1080),
+        ];
+
+        let after = rearrange_after_resize(&before, &[(1280, 720), (2560, 1440), (1920, 1080)]);
+
+        assert_eq!(after[1].x, 1280);
+        assert_eq!(after[2].x, 3840);
+    }
+
+    #[test]
+    fn display_below_follows_a_taller_one() {
+        let before = [rect(0, 0, 1920, 1080), rect(0, 1080, 1920, 1080)];
+
+        let after = rearrange_after_resize(&before, &[(1080, 1920), (1920, 1080)]);
+
+        assert_eq!(after[1], rect(0, 1920, 1920, 1080));
+    }
+
+    #[test]
+    fn display_after_a_gap_keeps_its_position() {
+        let before = [rect(0, 0, 1920, 1080), rect(2000, 0, 1920, 1080)];
+
+        let after = rearrange_after_resize(&before, &[(2560, 1440), (1920, 1080)]);
+
+        assert_eq!(after[1], before[1]);
+    }
+
+    #[test]
+    fn display_touching_only_at_a_corner_keeps_its_position() {
+        let before = [rect(0, 0, 1920, 1080), rect(1920, 1080, 1920, 1080)];
+
+        let after = rearrange_after_resize(&before, &[(2560, 1440), (1920, 1080)]);
+
+        assert_eq!(after[1], before[1]);
+    }
+
+    #[test]
+    fn overlapping_displays_keep_their_position() {
+        // Mirrored displays share the same origin, neither is on the right of the other
+        let before = [rect(0, 0, 1920, 1080), rect(0, 0, 1920, 1080)];
+
+        let after = rearrange_after_resize(&before, &[(1280, 720), (1920, 1080)]);
+
+        assert_eq!(after, [rect(0, 0, 1280, 720), rect(0, 0, 1920, 1080)]);
+    }
+
+    #[test]
+    fn partly_overlapping_display_keeps_its_position() {
+        let before = [rect(0, 0, 1920, 1080), rect(1000, 0, 1920, 1080)];
+
+        let after = rearrange_after_resize(&before, &[(2560, 1440), (1920, 1080)]);
+
+        assert_eq!(after[1], before[1]);
+    }
+}
@@ -1,6 +1,42 @@
-pub mod commands;
-pub mod cli;
-pub mod model;
-pub mod handles;
-pub mod head_printer;
-pub mod parsers;
+// The library only exposes `Client` and its types. The modules are shared with the wlout binary, which
+// declares them as well and uses what the library does not.
+#[allow(dead_code)]
+mod backends;
+#[allow(dead_code)]
+mod batch;
+#[allow(dead_code)]
+mod cli;
+mod client;
+#[allow(dead_code)]
+mod commands;
+#[allow(dead_code)]
+mod configuration;
+#[allow(dead_code)]
+mod edid;
+#[allow(dead_code)]
+mod handles;
+#[allow(dead_code)]
+mod head_printer;
+#[allow(dead_code)]
+mod history;
+#[allow(dead_code)]
+mod layout;
+#[allow(dead_code)]
+mod model;
+#[allow(dead_code)]
+mod parsers;
+#[allow(dead_code)]
+mod store;
+#[allow(dead_code)]
+mod suggestions;
+#[allow(dead_code)]
+mod timing;
+#[allow(dead_code)]
+mod trace;
+
+pub use client::{Client, ConfigurationBuilder, ConnectOptions, Error, Head, Mode};
+
+/// Used by the binaries generating the README and the command reference.
+#[cfg(feature = "markdown")]
+#[doc(hidden)]
+pub use cli::build_cli;
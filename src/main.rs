@@ -15,29 +15,52 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <https://www.gnu.org/licenses/>.
 
+mod edid;
 mod handles;
+mod history;
 mod parsers;
 
 mod backends;
+mod batch;
 mod cli;
 mod commands;
+mod configuration;
 mod head_printer;
+mod layout;
 mod model;
+mod store;
+mod suggestions;
+mod timing;
+mod trace;
 
-use crate::cli::{build_cli, NAME_ARG_ID};
+use crate::backends::wlroot::batch_command::{BatchCommand, BatchEntry};
+use crate::backends::wlroot::connect_trait::WaylandCommand;
+use crate::batch::read_batch_lines;
+use crate::cli::{NAME_ARG_ID, build_cli};
 use crate::commands::commands::{
-    Executable, InfoCommand, ListCommand, MirrorCommand, PowerCommand,
+    AllCommand, AutoCommand, DoctorCommand, Executable, GlobalOptions, HistoryCommand, InfoCommand,
+    ListCommand, MirrorCommand, OnlyCommand, PowerCommand, ProbeCommand, ScaleAutoCommand,
+    ScaleEqualizeCommand, UndoCommand, WaitCommand, WaitCondition,
 };
 use crate::commands::commands::{
-    ModeAutoCommand, ModeCurrentCommand, ModeListCommand, ModePreferredCommand, ModeSetCommand,
+    ModeAutoCommand, ModeCalcCommand, ModeCurrentCommand, ModeListCommand, ModePreferredCommand,
+    ModeSetCommand,
 };
 use crate::commands::commands::{
     MoveCommand, MoveRelativeCommand, REL_POS_ABOVE, REL_POS_BELOW, REL_POS_LEFT_OF,
     REL_POS_RIGHT_OF,
 };
 use crate::commands::completion_command::completion_command;
-use crate::model::HeadModeInput;
+use crate::edid::DEFAULT_SYSFS_ROOT;
+use crate::model::{HeadModeInput, parse_adaptive_sync, parse_transform};
+use crate::timing::{check_rate, check_size};
+use clap::ArgMatches;
+use clap::error::ErrorKind;
+use clap::parser::ValueSource;
+use std::fmt::Display;
+use std::path::PathBuf;
 use std::process::exit;
+use std::time::Duration;
 
 pub fn run() {
     let matches = build_cli().get_matches();
@@ -47,202 +70,630 @@ pub fn run() {
         return;
     }
 
+    let log_file = matches.get_one::<PathBuf>("log_file");
+    if let Err(error) = trace::init(matches.get_flag("debug"), log_file.map(PathBuf::as_path)) {
+        eprintln!("{}", error);
+        exit(1)
+    }
+    if let Err(error) = check_all_sockets(&matches) {
+        error.exit()
+    }
+
+    let options = GlobalOptions {
+        no_normalize: matches.get_flag("no_normalize"),
+        anchor: matches.get_one::<String>("anchor").cloned(),
+        force: matches.get_flag("force"),
+        plan: matches.get_flag("plan"),
+        strict: matches.get_flag("strict"),
+        force_apply: matches.get_flag("force_apply"),
+        retries: *matches.get_one::<u32>("retries").unwrap(),
+        timeout: matches.get_one::<Duration>("timeout").copied(),
+        socket: matches.get_one::<PathBuf>("socket").cloned(),
+        all_sockets: matches.get_flag("all_sockets"),
+    };
+
     match matches.subcommand() {
+        Some(("history", _)) => HistoryCommand {}.execute(&options),
+        Some(("mode", sub_matches)) if is_mode_calc(sub_matches) => {
+            let Some(("calc", sub_sub_matches)) = sub_matches.subcommand() else {
+                unreachable!()
+            };
+            let mode = sub_sub_matches.get_one::<HeadModeInput>("mode").unwrap();
+            if let Err(message) = check_size(mode.width, mode.height).and(check_rate(mode.rate)) {
+                usage_error("mode", ErrorKind::ValueValidation, message).exit()
+            }
+            ModeCalcCommand { mode: mode.clone() }.execute(&options);
+        }
+        Some(("batch", sub_matches)) => {
+            let source = sub_matches.get_one::<PathBuf>("file").unwrap();
+            let merge = sub_matches.get_flag("merge");
+            let keep_going = sub_matches.get_flag("keep_going");
+
+            let lines = read_batch_lines(source).unwrap_or_else(|error| {
+                eprintln!("{}", error);
+                exit(1)
+            });
+
+            let mut entries = vec![];
+            for line in lines {
+                match parse_batch_line(&line.words) {
+                    Ok((command, force)) => entries.push(BatchEntry {
+                        line_number: line.number,
+                        line: line.text,
+                        command,
+                        force,
+                    }),
+                    Err(error) => {
+                        eprint!("Line {} \"{}\": {}", line.number, line.text, error.render());
+                        // Nothing is run when the batch is not valid, unless asked to skip the invalid lines
+                        if !keep_going {
+                            exit(1)
+                        }
+                    }
+                }
+            }
+
+            BatchCommand {
+                entries,
+                merge,
+                keep_going,
+            }
+            .execute(&options)
+        }
+        _ => match command_from_matches(&matches) {
+            Ok(command) => command.execute(&options),
+            Err(error) => error.exit(),
+        },
+    }
+}
+
+/// Commands run against every compositor by `--all-sockets`, the ones that only query the displays.
+/// `wait` is left out: the sockets are run one after another, so it would block on the first one.
+const ALL_SOCKETS_COMMANDS: [&str; 2] = ["list", "doctor"];
+
+/// Refuses `--all-sockets` for the commands that change the displays, so that a single invocation cannot
+/// reconfigure every compositor on the machine.
+fn check_all_sockets(matches: &ArgMatches) -> Result<(), clap::Error> {
+    if !matches.get_flag("all_sockets") {
+        return Ok(());
+    }
+
+    match matches.subcommand() {
+        None => Ok(()),
+        Some((name, _)) if ALL_SOCKETS_COMMANDS.contains(&name) => Ok(()),
+        Some((name, _)) => Err(build_cli().error(
+            ErrorKind::ArgumentConflict,
+            format!(
+                "--all-sockets cannot be used with {}, only with {}",
+                name,
+                ALL_SOCKETS_COMMANDS.join(", ")
+            ),
+        )),
+    }
+}
+
+fn is_mode_calc(mode_matches: &ArgMatches) -> bool {
+    matches!(mode_matches.subcommand(), Some(("calc", _)))
+}
+
+/// Parses a line of a batch file into its command and whether the line itself asks to force it.
+fn parse_batch_line(words: &[String]) -> Result<(Box<dyn WaylandCommand>, bool), clap::Error> {
+    let line_matches = build_cli()
+        .no_binary_name(true)
+        .try_get_matches_from(words)?;
+    let command = batch_command_from_matches(&line_matches)?;
+
+    Ok((command, line_matches.get_flag("force")))
+}
+
+/// Same as `command_from_matches`, rejecting the commands that do not make sense in a batch.
+fn batch_command_from_matches(
+    matches: &ArgMatches,
+) -> Result<Box<dyn WaylandCommand>, clap::Error> {
+    match matches.subcommand() {
+        Some((name @ ("batch" | "completion" | "history"), _)) => Err(build_cli().error(
+            ErrorKind::InvalidSubcommand,
+            format!("{} cannot be used in a batch", name),
+        )),
+        Some(("mode", sub_matches)) if is_mode_calc(sub_matches) => Err(build_cli().error(
+            ErrorKind::InvalidSubcommand,
+            "mode calc cannot be used in a batch",
+        )),
+        _ => match batch_wide_option(matches) {
+            Some(option) => Err(build_cli().error(
+                ErrorKind::ArgumentConflict,
+                format!(
+                    "--{} applies to the whole batch, give it before batch instead of on a line",
+                    option
+                ),
+            )),
+            None => command_from_matches(matches),
+        },
+    }
+}
+
+/// Global options applying to the whole batch, eg. the connection or how the configuration is sent. `--force`
+/// is the only one a line can give for its own command.
+const BATCH_WIDE_OPTIONS: [&str; 10] = [
+    "no_normalize",
+    "anchor",
+    "plan",
+    "strict",
+    "force_apply",
+    "retries",
+    "timeout",
+    "socket",
+    "all_sockets",
+    "log_file",
+];
+
+/// The long name of the first batch wide option given on a line, which would be ignored.
+fn batch_wide_option(matches: &ArgMatches) -> Option<String> {
+    let id = BATCH_WIDE_OPTIONS
+        .iter()
+        .find(|id| matches.value_source(id) == Some(ValueSource::CommandLine))?;
+
+    build_cli()
+        .get_arguments()
+        .find(|arg| arg.get_id() == id)
+        .and_then(|arg| arg.get_long().map(String::from))
+}
+
+/// Builds the command to run against the compositor from the parsed command line.
+fn command_from_matches(matches: &ArgMatches) -> Result<Box<dyn WaylandCommand>, clap::Error> {
+    let command: Box<dyn WaylandCommand> = match matches.subcommand() {
         Some(("power", sub_matches)) => {
-            let name = sub_matches
-                .get_one::<String>(NAME_ARG_ID)
-                .expect(format!("{} is required", NAME_ARG_ID).as_str());
+            let name = sub_matches.get_one::<String>(NAME_ARG_ID).unwrap();
 
             let power_mode = sub_matches.get_one::<String>("power_mode").unwrap();
-            let force = sub_matches.get_one::<bool>("force").unwrap();
+
+            let mode = sub_matches.get_one::<HeadModeInput>("mode").cloned();
+            let position = sub_matches
+                .get_many::<i32>("position")
+                .map(|mut values| (*values.next().unwrap(), *values.next().unwrap()));
+            let scale = sub_matches.get_one::<f64>("scale").copied();
+            let transform = sub_matches
+                .get_one::<String>("transform")
+                .and_then(|value| parse_transform(value));
+            let adaptive_sync = sub_matches
+                .get_one::<String>("adaptive_sync")
+                .and_then(|value| parse_adaptive_sync(value));
 
             match power_mode.as_str() {
-                "on" => PowerCommand {
+                "on" => Box::new(PowerCommand {
                     name: name.clone(),
                     on: true,
-                    force: force.clone(),
-                }
-                .execute(),
-                "off" => PowerCommand {
-                    name: name.clone(),
-                    on: false,
-                    force: force.clone(),
-                }
-                .execute(),
-                &_ => {
-                    eprintln!("Power mode should be on / off");
-                    exit(1);
+                    mode,
+                    position,
+                    scale,
+                    transform,
+                    adaptive_sync,
+                }),
+                "off" => {
+                    if mode.is_some()
+                        || position.is_some()
+                        || scale.is_some()
+                        || transform.is_some()
+                        || adaptive_sync.is_some()
+                    {
+                        return Err(usage_error(
+                            "power",
+                            ErrorKind::ArgumentConflict,
+                            "mode, position, scale, transform and adaptive sync can only be set when turning a display on",
+                        ));
+                    }
+
+                    Box::new(PowerCommand {
+                        name: name.clone(),
+                        on: false,
+                        mode: None,
+                        position: None,
+                        scale: None,
+                        transform: None,
+                        adaptive_sync: None,
+                    })
                 }
+                &_ => unreachable!("Power mode should be on / off"),
             }
         }
+        Some(("only", sub_matches)) => {
+            let name = sub_matches.get_one::<String>(NAME_ARG_ID).unwrap();
+
+            Box::new(OnlyCommand { name: name.clone() })
+        }
+        Some(("all", _)) => Box::new(AllCommand {}),
+        Some(("auto", _)) => Box::new(AutoCommand {}),
+        Some(("doctor", _)) => Box::new(DoctorCommand {}),
+        Some(("wait", sub_matches)) => {
+            let name = sub_matches.get_one::<String>(NAME_ARG_ID).unwrap().clone();
+            let condition = sub_matches
+                .get_one::<WaitCondition>("condition")
+                .unwrap()
+                .clone();
+            let timeout = sub_matches.get_one::<Duration>("for").copied();
+
+            Box::new(WaitCommand {
+                name,
+                condition,
+                timeout,
+            })
+        }
+        Some(("probe", sub_matches)) => {
+            let name = sub_matches.get_one::<String>(NAME_ARG_ID).unwrap().clone();
+            let scale_step = scale_step(sub_matches, "probe")?;
+            let rate_step = *sub_matches.get_one::<i32>("rate_step").unwrap();
+            let max_rate = sub_matches.get_one::<i32>("max_rate").copied();
+
+            Box::new(ProbeCommand {
+                name,
+                scale_step,
+                rate_step,
+                max_rate,
+            })
+        }
+        Some(("undo", sub_matches)) => {
+            let index = *sub_matches.get_one::<u64>("n").unwrap() as usize;
+
+            Box::new(UndoCommand { index })
+        }
         Some(("list", sub_matches)) => {
-            let verbose = sub_matches.get_one::<bool>("verbose").unwrap().clone();
+            let verbose = *sub_matches.get_one::<bool>("verbose").unwrap();
+            let sysfs_root = sub_matches
+                .get_one::<PathBuf>("sysfs_root")
+                .unwrap()
+                .clone();
 
-            ListCommand { verbose }.execute()
+            Box::new(ListCommand {
+                verbose,
+                sysfs_root,
+            })
         }
         Some(("info", sub_matches)) => {
-            let name = sub_matches
-                .get_one::<String>(cli::NAME_ARG_ID)
-                .expect(format!("{} is required", cli::NAME_ARG_ID).as_str())
+            let name = sub_matches.get_one::<String>(NAME_ARG_ID).unwrap().clone();
+            let sysfs_root = sub_matches
+                .get_one::<PathBuf>("sysfs_root")
+                .unwrap()
                 .clone();
+            let parsable = sub_matches.get_flag("parsable");
 
-            InfoCommand { name }.execute();
+            Box::new(InfoCommand {
+                name,
+                sysfs_root,
+                parsable,
+            })
         }
         Some(("move", sub_matches)) => {
-            let name = sub_matches
-                .get_one::<String>(cli::NAME_ARG_ID)
-                .expect(format!("{} is required", cli::NAME_ARG_ID).as_str());
-
-            let sub_sub_matches = sub_matches.subcommand();
-            match sub_sub_matches {
-                Some((REL_POS_ABOVE, sub_sub_sub_matches)) => {
-                    let other_display = sub_sub_sub_matches
-                        .get_one::<String>("other_display")
-                        .unwrap();
-                    if name == other_display {
-                        eprintln!("The second display must be different !");
-                        exit(1);
-                    }
+            let name = sub_matches.get_one::<String>(NAME_ARG_ID).unwrap();
 
-                    MoveRelativeCommand {
-                        moved_display_name: name.clone(),
-                        reference_display_name: other_display.clone(),
-                        pos: REL_POS_ABOVE.to_string(),
-                    }
-                    .execute();
-                }
-                Some((REL_POS_BELOW, sub_sub_sub_matches)) => {
-                    let other_display = sub_sub_sub_matches
-                        .get_one::<String>("other_display")
-                        .unwrap();
-                    if name == other_display {
-                        eprintln!("The second display must be different !");
-                        exit(1);
-                    }
+            match sub_matches.subcommand() {
+                Some(("position", sub_sub_matches)) => {
+                    let x = *sub_sub_matches.get_one::<i32>("x").unwrap();
+                    let y = *sub_sub_matches.get_one::<i32>("y").unwrap();
 
-                    MoveRelativeCommand {
-                        moved_display_name: name.clone(),
-                        reference_display_name: other_display.clone(),
-                        pos: REL_POS_BELOW.to_string(),
-                    }
-                    .execute();
+                    Box::new(MoveCommand {
+                        name: name.clone(),
+                        x,
+                        y,
+                    })
                 }
-                Some(("right-of", sub_sub_sub_matches)) => {
-                    let other_display = sub_sub_sub_matches
-                        .get_one::<String>("other_display")
-                        .unwrap();
+                Some((pos, sub_sub_matches)) => {
+                    let other_display = sub_sub_matches.get_one::<String>("other_display").unwrap();
                     if name == other_display {
-                        eprintln!("The second display must be different !");
-                        exit(1);
+                        return Err(usage_error(
+                            "move",
+                            ErrorKind::ArgumentConflict,
+                            "The second display must be different !",
+                        ));
                     }
 
-                    MoveRelativeCommand {
-                        moved_display_name: name.clone(),
-                        reference_display_name: other_display.clone(),
-                        pos: REL_POS_RIGHT_OF.to_string(),
-                    }
-                    .execute();
-                }
-                Some(("left-of", sub_sub_sub_matches)) => {
-                    let other_display = sub_sub_sub_matches
-                        .get_one::<String>("other_display")
-                        .unwrap();
-                    if name == other_display {
-                        eprintln!("The second display must be different !");
-                        exit(1);
-                    }
+                    let pos = match pos {
+                        REL_POS_ABOVE => REL_POS_ABOVE,
+                        REL_POS_BELOW => REL_POS_BELOW,
+                        REL_POS_RIGHT_OF => REL_POS_RIGHT_OF,
+                        REL_POS_LEFT_OF => REL_POS_LEFT_OF,
+                        &_ => unreachable!("Unknown relative position {}", pos),
+                    };
 
-                    MoveRelativeCommand {
+                    Box::new(MoveRelativeCommand {
                         moved_display_name: name.clone(),
                         reference_display_name: other_display.clone(),
-                        pos: REL_POS_LEFT_OF.to_string(),
-                    }
-                    .execute();
-                }
-                Some(("position", sub_sub_sub_matches)) => {
-                    let x = sub_sub_sub_matches.get_one::<i32>("x").unwrap();
-                    let y = sub_sub_sub_matches.get_one::<i32>("y").unwrap();
-
-                    MoveCommand {
-                        name: name.clone(),
-                        x: x.clone(),
-                        y: y.clone(),
-                    }
-                    .execute();
+                        pos: pos.to_string(),
+                    })
                 }
-                None => todo!(),
-                Some((&_, _)) => todo!(),
+                None => unreachable!("move requires a subcommand"),
             }
         }
+        Some(("scale", sub_matches)) => match sub_matches.subcommand() {
+            Some(("equalize", sub_sub_matches)) => Box::new(ScaleEqualizeCommand {
+                names: sub_sub_matches
+                    .get_many::<String>("displays")
+                    .unwrap()
+                    .cloned()
+                    .collect(),
+                target_dpi: sub_sub_matches.get_one::<f64>("target_dpi").copied(),
+                step: scale_step(sub_sub_matches, "scale")?,
+            }),
+            Some(("auto", sub_sub_matches)) => {
+                let Some(name) = sub_matches.get_one::<String>(NAME_ARG_ID) else {
+                    return Err(missing_display_error("scale"));
+                };
+
+                Box::new(ScaleAutoCommand {
+                    name: name.clone(),
+                    target_dpi: *sub_sub_matches.get_one::<f64>("target_dpi").unwrap(),
+                    step: scale_step(sub_sub_matches, "scale")?,
+                })
+            }
+            _ => unreachable!("scale requires a subcommand"),
+        },
         Some(("mirror", sub_matches)) => {
-            let name = sub_matches
-                .get_one::<String>(NAME_ARG_ID)
-                .expect(format!("{} is required", NAME_ARG_ID).as_str());
+            let name = sub_matches.get_one::<String>(NAME_ARG_ID).unwrap();
 
             match sub_matches.subcommand() {
                 Some(("same-as", sub_sub_matches)) => {
                     let other_display = sub_sub_matches.get_one::<String>("other_display").unwrap();
                     if name == other_display {
-                        eprintln!("The second display must be different !");
-                        exit(1);
+                        return Err(usage_error(
+                            "mirror",
+                            ErrorKind::ArgumentConflict,
+                            "The second display must be different !",
+                        ));
                     }
 
-                    MirrorCommand {
+                    Box::new(MirrorCommand {
                         mirrored_display_name: name.clone(),
                         reference_display_name: other_display.clone(),
-                    }
-                    .execute()
+                    })
                 }
-                _ => {}
+                _ => unreachable!("mirror requires a subcommand"),
             }
         }
         Some(("mode", sub_matches)) => {
-            let name = sub_matches
-                .get_one::<String>(NAME_ARG_ID)
-                .expect(format!("{} is required", NAME_ARG_ID).as_str())
-                .clone();
+            let Some(name) = sub_matches.get_one::<String>(NAME_ARG_ID).cloned() else {
+                return Err(missing_display_error("mode"));
+            };
 
             match sub_matches.subcommand() {
-                Some(("current", _)) => {
-                    ModeCurrentCommand { name: name.clone() }.execute();
-                }
-                Some(("preferred", _)) => {
-                    ModePreferredCommand { name: name.clone() }.execute();
-                }
-                Some(("auto", _)) => {
-                    ModeAutoCommand { name: name.clone() }.execute();
-                }
-                Some(("list", _)) => {
-                    ModeListCommand { name: name.clone() }.execute();
-                }
+                Some(("current", _)) => Box::new(ModeCurrentCommand { name }),
+                Some(("preferred", _)) => Box::new(ModePreferredCommand { name }),
+                Some(("auto", _)) => Box::new(ModeAutoCommand { name }),
                 Some(("set", sub_sub_matches)) => {
-                    match sub_sub_matches.get_one::<HeadModeInput>("mode") {
-                        Some(mode) => {
-                            let force = *sub_sub_matches.get_one::<bool>("force").unwrap();
-                            ModeSetCommand {
-                                name: name.clone(),
-                                mode: mode.clone(),
-                                force,
-                            }
-                            .execute()
-                        }
-                        None => {}
-                    }
-                }
-                None => {
-                    ModeListCommand { name: name.clone() }.execute();
+                    let mode = sub_sub_matches.get_one::<HeadModeInput>("mode").unwrap();
+
+                    Box::new(ModeSetCommand {
+                        name,
+                        mode: mode.clone(),
+                    })
                 }
-                Some((&_, _)) => todo!(),
+                Some(("list", _)) | None => Box::new(ModeListCommand { name }),
+                Some((&_, _)) => unreachable!("Unknown mode subcommand"),
             }
         }
-        None => {
-            let verbose = matches.get_one::<bool>("verbose").unwrap().clone();
-            ListCommand { verbose }.execute()
-        }
+        // Only global options were given, list the displays like `wlout list` does
+        None => Box::new(ListCommand {
+            verbose: false,
+            sysfs_root: PathBuf::from(DEFAULT_SYSFS_ROOT),
+        }),
         _ => unreachable!("Exhausted list of subcommands and subcommand_required prevents `None`"),
+    };
+
+    Ok(command)
+}
+
+/// The `--step` the scales are rounded to, which has to be positive.
+fn scale_step(matches: &ArgMatches, command_name: &str) -> Result<f64, clap::Error> {
+    let step = *matches.get_one::<f64>("step").unwrap();
+    if step.is_nan() || step <= 0.0 {
+        return Err(usage_error(
+            command_name,
+            ErrorKind::ValueValidation,
+            "the step must be a positive number",
+        ));
     }
+
+    Ok(step)
+}
+
+/// Some commands take a display before their subcommand, except for a few subcommands that do not need one.
+fn missing_display_error(command_name: &str) -> clap::Error {
+    usage_error(
+        command_name,
+        ErrorKind::MissingRequiredArgument,
+        format!(
+            "the following required arguments were not provided:\n  <{}>",
+            NAME_ARG_ID
+        ),
+    )
+}
+
+/// An error reported with the usage of the command, like the ones raised by clap itself.
+fn usage_error(command_name: &str, kind: ErrorKind, message: impl Display) -> clap::Error {
+    let mut cli = build_cli();
+    cli.build();
+    cli.find_subcommand_mut(command_name)
+        .unwrap()
+        .error(kind, message)
 }
 
 fn main() {
     run()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> ArgMatches {
+        build_cli()
+            .try_get_matches_from(std::iter::once("wlout").chain(args.iter().copied()))
+            .unwrap()
+    }
+
+    #[test]
+    fn global_flags_alone_default_to_list() {
+        let flags: &[&[&str]] = &[
+            &["--no-normalize"],
+            &["--anchor", "DP-1"],
+            &["--plan"],
+            &["--strict"],
+            &["--force-apply"],
+            &["--retries", "1"],
+            &["--timeout", "2"],
+            &["--socket", "wayland-1"],
+            &["--all-sockets"],
+            &["-f"],
+            &["--force"],
+            &["-v"],
+            &["--debug"],
+            &["--log-file", "wlout.log"],
+        ];
+
+        for args in flags {
+            let matches = parse(args);
+            assert!(command_from_matches(&matches).is_ok(), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn all_sockets_only_runs_queries() {
+        for args in [
+            &["--all-sockets"][..],
+            &["--all-sockets", "list"],
+            &["--all-sockets", "doctor"],
+        ] {
+            assert!(check_all_sockets(&parse(args)).is_ok(), "{:?}", args);
+        }
+
+        for args in [
+            &["--all-sockets", "power", "DP-1", "off"][..],
+            &["--all-sockets", "mode", "DP-1", "auto"],
+            &["--all-sockets", "only", "DP-1"],
+            &["--all-sockets", "auto"],
+            &["--all-sockets", "undo"],
+            &["--all-sockets", "batch", "layout.txt"],
+            &["--all-sockets", "wait", "DP-1", "connected"],
+        ] {
+            assert!(check_all_sockets(&parse(args)).is_err(), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn wait_deadline_is_not_the_compositor_timeout() {
+        let matches = parse(&[
+            "--timeout",
+            "2",
+            "wait",
+            "DP-1",
+            "connected",
+            "--for",
+            "600",
+        ]);
+        let (_, wait_matches) = matches.subcommand().unwrap();
+
+        assert_eq!(
+            wait_matches.get_one::<Duration>("for"),
+            Some(&Duration::from_secs(600))
+        );
+        assert_eq!(
+            wait_matches.get_one::<Duration>("timeout"),
+            Some(&Duration::from_secs(2))
+        );
+    }
+
+    #[test]
+    fn debug_flag_is_not_list_verbose() {
+        let matches = parse(&["-v", "list", "-v"]);
+        assert!(matches.get_flag("debug"));
+        let Some(("list", list_matches)) = matches.subcommand() else {
+            panic!("list subcommand expected")
+        };
+        assert!(list_matches.get_flag("verbose"));
+
+        assert!(!parse(&["list", "-v"]).get_flag("debug"));
+    }
+
+    #[test]
+    fn scale_step_must_be_positive() {
+        for step in ["0", "-0.5", "NaN"] {
+            let step = format!("--step={}", step);
+            for args in [
+                &["scale", "DP-1", "auto", &step][..],
+                &["scale", "equalize", "DP-1", "DP-2", &step],
+                &["probe", "DP-1", &step],
+            ] {
+                let error = command_from_matches(&parse(args)).err();
+                assert_eq!(
+                    error.map(|error| error.kind()),
+                    Some(ErrorKind::ValueValidation),
+                    "{:?}",
+                    args
+                );
+            }
+        }
+
+        assert!(command_from_matches(&parse(&["scale", "DP-1", "auto", "--step=0.25"])).is_ok());
+    }
+
+    #[test]
+    fn adaptive_sync_uses_the_names_of_the_saved_states() {
+        for value in ["enabled", "disabled"] {
+            let args = ["power", "DP-1", "on", "--adaptive-sync", value];
+            assert!(command_from_matches(&parse(&args)).is_ok(), "{}", value);
+        }
+
+        let args = ["wlout", "power", "DP-1", "on", "--adaptive-sync", "on"];
+        assert!(build_cli().try_get_matches_from(args).is_err());
+    }
+
+    fn batch_line(line: &str) -> Result<(Box<dyn WaylandCommand>, bool), clap::Error> {
+        parse_batch_line(&crate::batch::split_words(line).unwrap())
+    }
+
+    #[test]
+    fn malformed_mode_in_a_batch_is_a_line_error() {
+        for line in [
+            "mode DP-1 set ax1@60",
+            "mode DP-1 set 1920xb@60",
+            "mode DP-1 set 1920x1080@c",
+            "mode DP-1 set 1920x1080",
+            "power DP-1 on --mode x@",
+        ] {
+            let error = batch_line(line).err();
+            assert_eq!(
+                error.map(|error| error.kind()),
+                Some(ErrorKind::ValueValidation),
+                "{}",
+                line
+            );
+        }
+
+        assert!(batch_line("mode DP-1 set 1920x1080@60").is_ok());
+    }
+
+    #[test]
+    fn batch_wide_options_are_refused_on_a_line() {
+        for line in [
+            "--no-normalize move DP-1 position 0 0",
+            "--anchor DP-1 move DP-1 position 0 0",
+            "mode DP-1 auto --plan",
+            "--strict scale DP-1 auto",
+            "power DP-1 on --force-apply",
+            "--retries 2 power DP-1 on",
+            "--timeout 3 power DP-1 on",
+            "--socket wayland-1 power DP-1 on",
+            "--all-sockets list",
+            "--log-file wlout.log list",
+        ] {
+            let error = batch_line(line).err();
+            assert_eq!(
+                error.map(|error| error.kind()),
+                Some(ErrorKind::ArgumentConflict),
+                "{}",
+                line
+            );
+        }
+
+        let (_, force) = batch_line("power DP-1 off --force").unwrap();
+        assert!(force);
+    }
+}
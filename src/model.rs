@@ -1,6 +1,11 @@
+use crate::commands::commands::{CommandError, GlobalOptions};
+use crate::configuration::Configuration;
+use crate::suggestions::closest_names;
 use std::cmp::Ordering;
 use std::collections::{HashMap, HashSet};
 use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use wayland_client::Connection;
 use wayland_client::backend::ObjectId;
 use wayland_client::protocol::wl_output::Transform;
 use wayland_protocols_wlr::output_management::v1::client::zwlr_output_head_v1::{
@@ -28,6 +33,52 @@ impl Display for HeadModeInput {
     }
 }
 
+pub static TRANSFORMS: [(&str, Transform); 8] = [
+    ("normal", Transform::Normal),
+    ("90", Transform::_90),
+    ("180", Transform::_180),
+    ("270", Transform::_270),
+    ("flipped", Transform::Flipped),
+    ("flipped-90", Transform::Flipped90),
+    ("flipped-180", Transform::Flipped180),
+    ("flipped-270", Transform::Flipped270),
+];
+
+pub fn transform_to_str(transform: Transform) -> &'static str {
+    TRANSFORMS
+        .iter()
+        .find(|(_, t)| *t == transform)
+        .map(|(name, _)| *name)
+        .unwrap_or("normal")
+}
+
+pub fn parse_transform(value: &str) -> Option<Transform> {
+    TRANSFORMS
+        .iter()
+        .find(|(name, _)| *name == value)
+        .map(|(_, t)| *t)
+}
+
+/// Names of the adaptive sync states, the same on the command line, in the saved states and in the output.
+pub static ADAPTIVE_SYNC_STATES: [(&str, AdaptiveSyncState); 2] = [
+    ("enabled", AdaptiveSyncState::Enabled),
+    ("disabled", AdaptiveSyncState::Disabled),
+];
+
+fn adaptive_sync_to_str(state: AdaptiveSyncState) -> &'static str {
+    match state {
+        AdaptiveSyncState::Enabled => "enabled",
+        _ => "disabled",
+    }
+}
+
+pub fn parse_adaptive_sync(value: &str) -> Option<AdaptiveSyncState> {
+    ADAPTIVE_SYNC_STATES
+        .iter()
+        .find(|(name, _)| *name == value)
+        .map(|(_, state)| *state)
+}
+
 #[derive(Debug, Clone)]
 pub struct HeadInfo {
     pub head: ZwlrOutputHeadV1,
@@ -47,18 +98,14 @@ pub struct HeadInfo {
     pub enabled: bool,
 }
 
-impl HeadInfo {
-    pub fn distance_to_origin(&self) -> i32 {
-        (self.position_x.unwrap().pow(2) + self.position_y.unwrap().pow(2)).isqrt()
-    }
-}
-
 #[derive(Debug, Clone, Hash)]
 pub struct HeadMode {
     pub mode: Option<ZwlrOutputModeV1>,
     pub height: i32,
     pub width: i32,
     pub rate: i32,
+    /// Refresh rate in mHz as advertised, `rate` is it truncated to Hz.
+    pub refresh: i32,
     pub is_preferred: bool,
     pub is_current: bool,
 }
@@ -92,21 +139,56 @@ pub enum ConfigResult {
 
 #[derive(Debug)]
 pub struct AppData {
+    pub connection: Connection,
     pub initial_done: bool,
     pub heads: HashMap<ObjectId, HeadInfo>,
     pub manager: Option<ZwlrOutputManagerV1>,
     pub config_result: Option<ConfigResult>,
     pub config_serial: Option<u32>,
     pub output_manager_found: bool,
+    /// Version of the output manager protocol bound, the heads and modes share it.
+    pub manager_version: u32,
+    /// Interface name and version of every global the compositor advertised.
+    pub globals: Vec<(String, u32)>,
+    pub options: GlobalOptions,
+    /// Collects the changes of the commands of a merged batch, applied together at the end.
+    pub batch_configuration: Option<Configuration>,
+    /// States to add to a state file once the merged configuration of the batch is applied.
+    pub batch_saved_states: Vec<(&'static str, Vec<HeadState>)>,
 }
 
 impl AppData {
-    pub fn get_head(&self, name: &str) -> HeadInfo {
-        self.heads
+    /// Whether the bound output manager version has a request or event added in version `since`.
+    pub fn supports(&self, since: u32) -> bool {
+        self.manager_version >= since
+    }
+
+    /// The connected head with this name, or an error suggesting the closest names.
+    pub fn get_head(&self, name: &str) -> Result<HeadInfo, CommandError> {
+        if let Some(head_info) = self
+            .heads
             .values()
             .find(|head_info| head_info.name.as_deref() == Some(name))
-            .expect(&*format!("Display \"{}\" not found", name))
-            .clone()
+        {
+            return Ok(head_info.clone());
+        }
+
+        let mut names: Vec<&str> = self
+            .heads
+            .values()
+            .filter_map(|head_info| head_info.name.as_deref())
+            .collect();
+        names.sort();
+
+        let mut message = format!("Display \"{}\" not found", name);
+        let suggestions = closest_names(name, names.iter().copied());
+        if !suggestions.is_empty() {
+            message += &format!(", did you mean {} ?", suggestions.join(", "));
+        } else if !names.is_empty() {
+            message += &format!(". Connected displays: {}", names.join(", "));
+        }
+
+        Err(CommandError::InvalidRequest(message))
     }
 
     #[allow(dead_code)]
@@ -116,6 +198,25 @@ impl AppData {
             .find(|head_info| head_info.name.as_deref() == Some(name))
             .clone()
     }
+
+    /// Finds the connected head a saved state belongs to.
+    /// A display that reports the same make, model and serial on the same connector wins, then one
+    /// with the same make, model and serial anywhere, and finally whatever is plugged on the connector.
+    pub fn find_head_for_state(&self, state: &HeadState) -> Option<&HeadInfo> {
+        let identity = state.identity();
+        let same_identity = |head: &&HeadInfo| identity.is_some() && head.identity() == identity;
+        let same_name = |head: &&HeadInfo| head.name.as_deref() == Some(state.name.as_str());
+
+        self.heads
+            .values()
+            .find(|head| same_identity(head) && same_name(head))
+            .or_else(|| self.heads.values().find(same_identity))
+            .or_else(|| {
+                self.heads.values().find(|head| {
+                    same_name(head) && (identity.is_none() || head.identity().is_none())
+                })
+            })
+    }
 }
 
 impl HeadInfo {
@@ -148,6 +249,436 @@ impl HeadInfo {
             .values()
             .find(|m| m.width == width && m.height == height && m.rate == rate)
     }
+
+    /// Size taken by the head in the global compositor space once scale and transform are applied.
+    pub fn logical_size(&self) -> Option<(i32, i32)> {
+        self.get_current_mode()
+            .map(|mode| logical_size(mode.width, mode.height, self.scale, self.transform))
+    }
+
+    /// Diagonal of the panel in inches, from the physical size advertised in millimeters.
+    pub fn diagonal_inches(&self) -> Option<f64> {
+        let (width, height) = self.physical_width.zip(self.physical_height)?;
+        diagonal_inches(width, height)
+    }
+
+    /// Pixels per inch of the panel at the current mode (or the mode it would be turned on with).
+    pub fn ppi(&self) -> Option<f64> {
+        let mode = self.get_current_or_preferred_mode()?;
+        ppi(mode.width, mode.height, self.diagonal_inches()?)
+    }
+
+    /// Dots per inch seen by applications once the scale is applied.
+    pub fn logical_dpi(&self) -> Option<f64> {
+        Some(logical_dpi(self.ppi()?, self.scale))
+    }
+
+    /// Aspect ratio of the current mode, using the usual marketing names when close enough (21:9 for 3440x1440).
+    pub fn aspect_ratio(&self) -> Option<String> {
+        let mode = self.get_current_or_preferred_mode()?;
+        aspect_ratio(mode.width, mode.height)
+    }
+
+    /// The advertised modes closest to the requested one, by resolution first and then refresh rate.
+    pub fn nearest_modes(&self, mode: &HeadModeInput, count: usize) -> Vec<&HeadMode> {
+        nearest_modes(self.modes.values(), mode, count)
+    }
+
+    pub fn get_preferred_mode(&self) -> Option<&HeadMode> {
+        self.modes.values().find(|m| m.is_preferred)
+    }
+
+    /// Current mode when the head is on, otherwise the mode it should be switched on with:
+    /// the preferred one, or the biggest advertised mode as a last resort.
+    pub fn get_current_or_preferred_mode(&self) -> Option<&HeadMode> {
+        self.get_current_mode()
+            .or_else(|| self.get_preferred_mode())
+            .or_else(|| self.modes.values().min_by(|a, b| compare_modes(a, b)))
+    }
+
+    /// Make, model and serial joined together, used to recognize a display across connectors.
+    /// `None` when the compositor does not advertise any of them.
+    pub fn identity(&self) -> Option<String> {
+        identity_of(&self.make, &self.model, &self.serial)
+    }
+}
+
+const MM_PER_INCH: f64 = 25.4;
+
+/// Diagonal in inches of a panel of this physical size in millimeters.
+pub fn diagonal_inches(width_mm: i32, height_mm: i32) -> Option<f64> {
+    // Projectors and virtual outputs advertise 0x0
+    if width_mm <= 0 || height_mm <= 0 {
+        return None;
+    }
+
+    Some((width_mm as f64).hypot(height_mm as f64) / MM_PER_INCH)
+}
+
+/// Pixels per inch of a mode shown on a panel with this diagonal.
+pub fn ppi(width: i32, height: i32, diagonal_inches: f64) -> Option<f64> {
+    if width <= 0 || height <= 0 || diagonal_inches <= 0.0 {
+        return None;
+    }
+
+    Some((width as f64).hypot(height as f64) / diagonal_inches)
+}
+
+/// Dots per inch seen by applications, a missing or invalid scale counting as 1.
+pub fn logical_dpi(ppi: f64, scale: Option<f64>) -> f64 {
+    ppi / scale.filter(|scale| *scale > 0.0).unwrap_or(1.0)
+}
+
+static COMMON_ASPECT_RATIOS: [(i32, i32); 9] = [
+    (4, 3),
+    (5, 4),
+    (3, 2),
+    (16, 10),
+    (16, 9),
+    (17, 9),
+    (21, 9),
+    (32, 9),
+    (32, 10),
+];
+
+pub fn aspect_ratio(width: i32, height: i32) -> Option<String> {
+    if width <= 0 || height <= 0 {
+        return None;
+    }
+
+    let ratio = width as f64 / height as f64;
+    if let Some((w, h)) = COMMON_ASPECT_RATIOS
+        .iter()
+        .find(|(w, h)| (ratio / (*w as f64 / *h as f64) - 1.0).abs() < 0.03)
+    {
+        return Some(format!("{}:{}", w, h));
+    }
+
+    let gcd = gcd(width, height);
+    Some(format!("{}:{}", width / gcd, height / gcd))
+}
+
+fn gcd(a: i32, b: i32) -> i32 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+pub fn logical_size(
+    width: i32,
+    height: i32,
+    scale: Option<f64>,
+    transform: Option<Transform>,
+) -> (i32, i32) {
+    let scale = scale.filter(|scale| *scale > 0.0).unwrap_or(1.0);
+    let width = (width as f64 / scale).round() as i32;
+    let height = (height as f64 / scale).round() as i32;
+
+    match transform {
+        Some(Transform::_90 | Transform::_270 | Transform::Flipped90 | Transform::Flipped270) => {
+            (height, width)
+        }
+        _ => (width, height),
+    }
+}
+
+fn identity_of(
+    make: &Option<String>,
+    model: &Option<String>,
+    serial: &Option<String>,
+) -> Option<String> {
+    let parts = [make, model, serial].map(|part| part.as_deref().unwrap_or("").trim());
+
+    if parts.iter().all(|part| part.is_empty()) {
+        None
+    } else {
+        Some(parts.join("|"))
+    }
+}
+
+/// A snapshot of the configurable properties of a head.
+///
+/// It is written to the wlout state directory as a single line of tab separated `key=value` pairs so
+/// that a display can be put back the way it was.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HeadState {
+    pub name: String,
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub serial: Option<String>,
+    pub enabled: bool,
+    pub mode: Option<HeadModeInput>,
+    /// Refresh rate of the mode in mHz when known, more precise than its rate in Hz. It is not saved.
+    pub refresh: Option<i32>,
+    pub position: Option<(i32, i32)>,
+    pub scale: Option<f64>,
+    pub transform: Option<Transform>,
+    pub adaptive_sync: Option<AdaptiveSyncState>,
+}
+
+/// Scales are sent back by the compositor as fixed point numbers, in steps of 1/256.
+pub const SCALE_TOLERANCE: f64 = 1.0 / 256.0;
+/// Custom modes are rounded by the compositor to the timings the display can do, eg. 75 Hz to 74.97 Hz.
+/// Small enough to still tell 59.94 Hz from 60 Hz.
+pub const REFRESH_TOLERANCE_MHZ: i32 = 50;
+
+/// A property of a display that differs between two of its states.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PropertyChange {
+    pub property: &'static str,
+    pub before: String,
+    pub after: String,
+}
+
+impl HeadState {
+    /// The properties that differ in the other state. Only the power state is compared when the display
+    /// ends up off, and the properties it had while off are not shown since they do not apply.
+    pub fn changes_to(&self, after: &HeadState) -> Vec<PropertyChange> {
+        let mut changes = vec![];
+        let mut compare =
+            |property: &'static str, before: Option<String>, after: Option<String>| {
+                if before != after {
+                    changes.push(PropertyChange {
+                        property,
+                        before: before.unwrap_or(String::from("N/A")),
+                        after: after.unwrap_or(String::from("N/A")),
+                    });
+                }
+            };
+
+        let on_off = |enabled: bool| String::from(if enabled { "on" } else { "off" });
+        compare(
+            "Enabled",
+            Some(on_off(self.enabled)),
+            Some(on_off(after.enabled)),
+        );
+        if !after.enabled {
+            return changes;
+        }
+
+        let before = self.enabled.then_some(self);
+        // The compositor rounds the refresh rate and scale it reports, small differences are no change
+        let same_mode = before.is_some_and(|state| state.has_same_mode(after));
+        if !same_mode {
+            compare(
+                "Mode",
+                before.and_then(|state| state.mode.as_ref().map(ToString::to_string)),
+                after.mode.as_ref().map(ToString::to_string),
+            );
+        }
+        compare(
+            "Position",
+            before.and_then(|state| state.position.map(|(x, y)| format!("{},{}", x, y))),
+            after.position.map(|(x, y)| format!("{},{}", x, y)),
+        );
+        let same_scale = before.is_some_and(|state| state.has_same_scale(after));
+        if !same_scale {
+            compare(
+                "Scale",
+                before.and_then(|state| state.scale.map(|scale| scale.to_string())),
+                after.scale.map(|scale| scale.to_string()),
+            );
+        }
+        compare(
+            "Transform",
+            before.and_then(|state| state.transform.map(|t| transform_to_str(t).to_string())),
+            after.transform.map(|t| transform_to_str(t).to_string()),
+        );
+        compare(
+            "VRR",
+            before.and_then(|state| {
+                state
+                    .adaptive_sync
+                    .map(|vrr| adaptive_sync_to_str(vrr).to_string())
+            }),
+            after
+                .adaptive_sync
+                .map(|vrr| adaptive_sync_to_str(vrr).to_string()),
+        );
+
+        changes
+    }
+
+    /// Whether both states have the same mode, comparing the refresh rates in mHz within
+    /// `REFRESH_TOLERANCE_MHZ` when both are known.
+    fn has_same_mode(&self, other: &HeadState) -> bool {
+        let (Some(mode), Some(other_mode)) = (&self.mode, &other.mode) else {
+            return self.mode.is_none() && other.mode.is_none();
+        };
+        if mode.width != other_mode.width || mode.height != other_mode.height {
+            return false;
+        }
+
+        match (self.refresh, other.refresh) {
+            (Some(refresh), Some(other_refresh)) => {
+                (refresh - other_refresh).abs() <= REFRESH_TOLERANCE_MHZ
+            }
+            _ => mode.rate == other_mode.rate,
+        }
+    }
+
+    /// Whether both states have the same scale within `SCALE_TOLERANCE`.
+    fn has_same_scale(&self, other: &HeadState) -> bool {
+        match (self.scale, other.scale) {
+            (Some(scale), Some(other_scale)) => (scale - other_scale).abs() <= SCALE_TOLERANCE,
+            (scale, other_scale) => scale.is_none() && other_scale.is_none(),
+        }
+    }
+
+    pub fn identity(&self) -> Option<String> {
+        identity_of(&self.make, &self.model, &self.serial)
+    }
+
+    /// Whether both states were taken from the same physical display.
+    pub fn is_same_display(&self, other: &HeadState) -> bool {
+        match (self.identity(), other.identity()) {
+            (Some(identity), Some(other_identity)) => identity == other_identity,
+            _ => self.name == other.name,
+        }
+    }
+
+    pub fn logical_size(&self) -> Option<(i32, i32)> {
+        self.mode
+            .as_ref()
+            .map(|mode| logical_size(mode.width, mode.height, self.scale, self.transform))
+    }
+}
+
+impl From<&HeadInfo> for HeadState {
+    fn from(head: &HeadInfo) -> Self {
+        HeadState {
+            name: head.name.clone().unwrap_or_default(),
+            make: head.make.clone(),
+            model: head.model.clone(),
+            serial: head.serial.clone(),
+            enabled: head.enabled,
+            mode: head.get_current_mode().map(|mode| HeadModeInput {
+                width: mode.width,
+                height: mode.height,
+                rate: mode.rate,
+            }),
+            refresh: head.get_current_mode().map(|mode| mode.refresh),
+            position: head.position_x.zip(head.position_y),
+            scale: head.scale,
+            transform: head.transform,
+            adaptive_sync: head.adaptive_sync,
+        }
+    }
+}
+
+impl Display for HeadState {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let sanitize = |value: &str| value.replace(['\t', '\n'], " ");
+
+        write!(f, "name={}", sanitize(&self.name))?;
+        if let Some(make) = &self.make {
+            write!(f, "\tmake={}", sanitize(make))?;
+        }
+        if let Some(model) = &self.model {
+            write!(f, "\tmodel={}", sanitize(model))?;
+        }
+        if let Some(serial) = &self.serial {
+            write!(f, "\tserial={}", sanitize(serial))?;
+        }
+        write!(f, "\tenabled={}", self.enabled)?;
+        if let Some(mode) = &self.mode {
+            write!(f, "\tmode={}", mode)?;
+        }
+        if let Some((x, y)) = self.position {
+            write!(f, "\tposition={},{}", x, y)?;
+        }
+        if let Some(scale) = self.scale {
+            write!(f, "\tscale={}", scale)?;
+        }
+        if let Some(transform) = self.transform {
+            write!(f, "\ttransform={}", transform_to_str(transform))?;
+        }
+        if let Some(adaptive_sync) = self.adaptive_sync {
+            write!(f, "\tadaptive_sync={}", adaptive_sync_to_str(adaptive_sync))?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for HeadState {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let mut state = HeadState {
+            name: String::new(),
+            make: None,
+            model: None,
+            serial: None,
+            enabled: false,
+            mode: None,
+            refresh: None,
+            position: None,
+            scale: None,
+            transform: None,
+            adaptive_sync: None,
+        };
+
+        for field in line.split('\t') {
+            let (key, value) = field
+                .split_once('=')
+                .ok_or(format!("Malformed field \"{}\"", field))?;
+
+            match key {
+                "name" => state.name = value.to_string(),
+                "make" => state.make = Some(value.to_string()),
+                "model" => state.model = Some(value.to_string()),
+                "serial" => state.serial = Some(value.to_string()),
+                "enabled" => state.enabled = value == "true",
+                "mode" => state.mode = Some(parse_mode(value)?),
+                "position" => {
+                    let (x, y) = value
+                        .split_once(',')
+                        .ok_or(format!("Malformed position \"{}\"", value))?;
+                    state.position = Some((
+                        x.parse()
+                            .map_err(|_| format!("Malformed position \"{}\"", value))?,
+                        y.parse()
+                            .map_err(|_| format!("Malformed position \"{}\"", value))?,
+                    ))
+                }
+                "scale" => {
+                    state.scale = Some(
+                        value
+                            .parse()
+                            .map_err(|_| format!("Malformed scale \"{}\"", value))?,
+                    )
+                }
+                "transform" => {
+                    state.transform = Some(
+                        parse_transform(value).ok_or(format!("Unknown transform \"{}\"", value))?,
+                    )
+                }
+                "adaptive_sync" => {
+                    state.adaptive_sync = Some(
+                        parse_adaptive_sync(value)
+                            .ok_or(format!("Unknown adaptive sync state \"{}\"", value))?,
+                    )
+                }
+                _ => {}
+            }
+        }
+
+        if state.name.is_empty() {
+            return Err(String::from("Missing display name"));
+        }
+
+        Ok(state)
+    }
+}
+
+fn parse_mode(value: &str) -> Result<HeadModeInput, String> {
+    let error = || format!("Malformed mode \"{}\"", value);
+    let (size, rate) = value.split_once('@').ok_or_else(error)?;
+    let (width, height) = size.split_once('x').ok_or_else(error)?;
+
+    Ok(HeadModeInput {
+        width: width.parse().map_err(|_| error())?,
+        height: height.parse().map_err(|_| error())?,
+        rate: rate.parse().map_err(|_| error())?,
+    })
 }
 
 pub fn get_common_modes(
@@ -175,6 +706,26 @@ pub fn get_common_modes(
     (result1.into_iter().collect(), result2.into_iter().collect())
 }
 
+/// The `count` modes closest to the requested one, by resolution first and then refresh rate.
+/// Between equally close refresh rates the faster mode comes first.
+pub fn nearest_modes<'a>(
+    modes: impl Iterator<Item = &'a HeadMode>,
+    mode: &HeadModeInput,
+    count: usize,
+) -> Vec<&'a HeadMode> {
+    let mut modes: Vec<&HeadMode> = modes.collect();
+    modes.sort_by_key(|m| {
+        (
+            (m.width - mode.width).abs() + (m.height - mode.height).abs(),
+            (m.rate - mode.rate).abs(),
+            std::cmp::Reverse(m.rate),
+        )
+    });
+    modes.truncate(count);
+
+    modes
+}
+
 fn compare_modes(mode: &HeadMode, other: &HeadMode) -> Ordering {
     other
         .height
@@ -194,3 +745,189 @@ pub fn get_best_display_modes(
 
     (max_1, max_2)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(mode: (i32, i32, i32), refresh: Option<i32>, scale: f64) -> HeadState {
+        HeadState {
+            name: String::from("DP-1"),
+            make: None,
+            model: None,
+            serial: None,
+            enabled: true,
+            mode: Some(HeadModeInput {
+                width: mode.0,
+                height: mode.1,
+                rate: mode.2,
+            }),
+            refresh,
+            position: Some((0, 0)),
+            scale: Some(scale),
+            transform: Some(Transform::Normal),
+            adaptive_sync: None,
+        }
+    }
+
+    /// The scale as sent back by the compositor, a wl_fixed in steps of 1/256.
+    fn fixed(scale: f64) -> f64 {
+        (scale * 256.0) as i32 as f64 / 256.0
+    }
+
+    #[test]
+    fn scale_rounded_to_fixed_point_is_no_change() {
+        for scale in [1.1, 1.2, 1.25, 1.5, 1.75] {
+            let requested = state((1920, 1080, 60), Some(60000), scale);
+            let applied = state((1920, 1080, 60), Some(60000), fixed(scale));
+            assert!(requested.changes_to(&applied).is_empty(), "{}", scale);
+        }
+    }
+
+    #[test]
+    fn different_scale_is_a_change() {
+        let requested = state((1920, 1080, 60), Some(60000), 1.5);
+        let applied = state((1920, 1080, 60), Some(60000), 1.25);
+
+        let changes = requested.changes_to(&applied);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].property, "Scale");
+    }
+
+    #[test]
+    fn custom_refresh_rounded_by_the_compositor_is_no_change() {
+        let requested = state((1920, 1080, 75), Some(75000), 1.0);
+        let applied = state((1920, 1080, 74), Some(74970), 1.0);
+
+        assert!(requested.changes_to(&applied).is_empty());
+    }
+
+    #[test]
+    fn close_refresh_rates_are_still_told_apart() {
+        let requested = state((1920, 1080, 60), Some(60000), 1.0);
+        let applied = state((1920, 1080, 59), Some(59940), 1.0);
+
+        let changes = requested.changes_to(&applied);
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].property, "Mode");
+    }
+
+    #[test]
+    fn refresh_rates_in_hz_are_compared_when_mhz_are_unknown() {
+        let saved = state((1920, 1080, 60), None, 1.0);
+        let current = state((1920, 1080, 60), Some(60000), 1.0);
+
+        assert!(saved.changes_to(&current).is_empty());
+    }
+
+    #[test]
+    fn ppi_of_a_27_inch_1440p_panel() {
+        // 597x336 mm is the physical size advertised by most 27" 16:9 panels
+        let diagonal = diagonal_inches(597, 336).unwrap();
+        assert!((diagonal - 27.0).abs() < 0.1, "{}", diagonal);
+
+        let ppi = ppi(2560, 1440, diagonal).unwrap();
+        assert!((ppi - 108.8).abs() < 0.2, "{}", ppi);
+        assert!((logical_dpi(ppi, Some(1.25)) - ppi / 1.25).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn no_physical_size_gives_no_metrics() {
+        assert_eq!(diagonal_inches(0, 0), None);
+        assert_eq!(diagonal_inches(597, 0), None);
+        assert_eq!(ppi(2560, 1440, 0.0), None);
+    }
+
+    #[test]
+    fn invalid_scale_counts_as_one() {
+        assert_eq!(logical_dpi(96.0, None), 96.0);
+        assert_eq!(logical_dpi(96.0, Some(0.0)), 96.0);
+        assert_eq!(logical_dpi(96.0, Some(2.0)), 48.0);
+    }
+
+    #[test]
+    fn aspect_ratio_uses_the_marketing_names() {
+        assert_eq!(aspect_ratio(2560, 1440).as_deref(), Some("16:9"));
+        assert_eq!(aspect_ratio(3440, 1440).as_deref(), Some("21:9"));
+        assert_eq!(aspect_ratio(1920, 1200).as_deref(), Some("16:10"));
+        assert_eq!(aspect_ratio(0, 1080), None);
+    }
+
+    #[test]
+    fn adaptive_sync_survives_a_save() {
+        for (name, adaptive_sync) in ADAPTIVE_SYNC_STATES {
+            let saved = HeadState {
+                adaptive_sync: Some(adaptive_sync),
+                ..state((1920, 1080, 60), None, 1.0)
+            };
+
+            let line = saved.to_string();
+            assert!(
+                line.ends_with(&format!("\tadaptive_sync={}", name)),
+                "{}",
+                line
+            );
+            assert_eq!(
+                line.parse::<HeadState>().unwrap().adaptive_sync,
+                Some(adaptive_sync)
+            );
+        }
+    }
+
+    fn advertised(width: i32, height: i32, rate: i32) -> HeadMode {
+        HeadMode {
+            mode: None,
+            width,
+            height,
+            rate,
+            refresh: rate * 1000,
+            is_preferred: false,
+            is_current: false,
+        }
+    }
+
+    #[test]
+    fn nearest_modes_prefer_the_resolution_over_the_rate() {
+        let modes = [
+            advertised(3840, 2160, 60),
+            advertised(2560, 1440, 144),
+            advertised(2560, 1440, 60),
+            advertised(1920, 1080, 75),
+        ];
+        let requested = HeadModeInput {
+            width: 2560,
+            height: 1440,
+            rate: 75,
+        };
+
+        let nearest: Vec<String> = nearest_modes(modes.iter(), &requested, 3)
+            .iter()
+            .map(|mode| mode.to_string())
+            .collect();
+        assert_eq!(nearest, ["2560x1440@60", "2560x1440@144", "1920x1080@75"]);
+    }
+
+    #[test]
+    fn equally_close_rates_suggest_the_faster_mode_first() {
+        let modes = [advertised(1920, 1080, 50), advertised(1920, 1080, 70)];
+        let requested = HeadModeInput {
+            width: 1920,
+            height: 1080,
+            rate: 60,
+        };
+
+        let nearest = nearest_modes(modes.iter(), &requested, 1);
+        assert_eq!(nearest[0].rate, 70);
+    }
+
+    #[test]
+    fn no_advertised_mode_gives_no_suggestion() {
+        let requested = HeadModeInput {
+            width: 1920,
+            height: 1080,
+            rate: 60,
+        };
+
+        assert!(nearest_modes([].iter(), &requested, 3).is_empty());
+    }
+}
@@ -1,8 +1,10 @@
+use crate::commands::commands::WaitCondition;
 use crate::model::HeadModeInput;
 use clap::builder::TypedValueParser;
 use clap::error::{ContextKind, ContextValue, ErrorKind};
 use clap::{Arg, Command, Error};
 use std::ffi::OsStr;
+use std::time::Duration;
 
 #[derive(Debug, Clone, Copy)]
 pub struct DisplayModeParser {}
@@ -16,42 +18,196 @@ impl TypedValueParser for DisplayModeParser {
         arg: Option<&Arg>,
         value: &OsStr,
     ) -> Result<Self::Value, Error> {
-        let parts = value.to_str().unwrap().split("@").collect::<Vec<&str>>();
-        if parts.len() != 2 {
+        let invalid_mode = || {
             let mut error = Error::new(ErrorKind::ValueValidation).with_cmd(cmd);
+            if let Some(arg) = arg {
+                error.insert(
+                    ContextKind::InvalidArg,
+                    ContextValue::String(arg.to_string()),
+                );
+            }
             error.insert(
-                ContextKind::InvalidArg,
-                ContextValue::String(arg.unwrap().to_string()),
+                ContextKind::InvalidValue,
+                ContextValue::String(value.to_string_lossy().to_string()),
             );
 
-            return Err(error);
+            error
+        };
+
+        let (size_part, rate_part) = value
+            .to_str()
+            .and_then(|value| value.split_once('@'))
+            .ok_or_else(invalid_mode)?;
+        let (width_part, height_part) = size_part.split_once('x').ok_or_else(invalid_mode)?;
+
+        let number = |part: &str| part.parse::<i32>().map_err(|_| invalid_mode());
+
+        Ok(HeadModeInput {
+            width: number(width_part)?,
+            height: number(height_part)?,
+            rate: number(rate_part)?,
+        })
+    }
+}
+
+/// Parses the condition of the wait command: connected, disconnected, enabled, disabled or
+/// mode=<WIDTH>x<HEIGHT>@<RATE>.
+#[derive(Debug, Clone, Copy)]
+pub struct WaitConditionParser {}
+
+impl TypedValueParser for WaitConditionParser {
+    type Value = WaitCondition;
+
+    fn parse_ref(
+        &self,
+        cmd: &Command,
+        arg: Option<&Arg>,
+        value: &OsStr,
+    ) -> Result<Self::Value, Error> {
+        let condition = match value.to_str().unwrap_or_default() {
+            "connected" => WaitCondition::Connected,
+            "disconnected" => WaitCondition::Disconnected,
+            "enabled" => WaitCondition::Enabled,
+            "disabled" => WaitCondition::Disabled,
+            other => match other.strip_prefix("mode=") {
+                Some(mode) => WaitCondition::Mode(DisplayModeParser {}.parse_ref(
+                    cmd,
+                    arg,
+                    OsStr::new(mode),
+                )?),
+                None => {
+                    let mut error = Error::new(ErrorKind::InvalidValue).with_cmd(cmd);
+                    if let Some(arg) = arg {
+                        error.insert(
+                            ContextKind::InvalidArg,
+                            ContextValue::String(arg.to_string()),
+                        );
+                    }
+                    error.insert(
+                        ContextKind::InvalidValue,
+                        ContextValue::String(other.to_string()),
+                    );
+                    error.insert(
+                        ContextKind::ValidValue,
+                        ContextValue::Strings(
+                            [
+                                "connected",
+                                "disconnected",
+                                "enabled",
+                                "disabled",
+                                "mode=WxH@R",
+                            ]
+                            .map(String::from)
+                            .to_vec(),
+                        ),
+                    );
+
+                    return Err(error);
+                }
+            },
+        };
+
+        Ok(condition)
+    }
+}
+
+/// Parses a positive number of seconds, eg. `2.5`, into a duration.
+#[derive(Debug, Clone, Copy)]
+pub struct SecondsParser {}
+
+impl TypedValueParser for SecondsParser {
+    type Value = Duration;
+
+    fn parse_ref(
+        &self,
+        cmd: &Command,
+        arg: Option<&Arg>,
+        value: &OsStr,
+    ) -> Result<Self::Value, Error> {
+        let text = value.to_str().unwrap_or_default();
+        match text.parse::<f64>().map(Duration::try_from_secs_f64) {
+            Ok(Ok(duration)) if !duration.is_zero() => Ok(duration),
+            _ => {
+                let mut error = Error::new(ErrorKind::ValueValidation).with_cmd(cmd);
+                if let Some(arg) = arg {
+                    error.insert(
+                        ContextKind::InvalidArg,
+                        ContextValue::String(arg.to_string()),
+                    );
+                }
+                error.insert(
+                    ContextKind::InvalidValue,
+                    ContextValue::String(text.to_string()),
+                );
+                error.insert(
+                    ContextKind::Custom,
+                    ContextValue::String(String::from(
+                        "the timeout must be a positive number of seconds",
+                    )),
+                );
+
+                Err(error)
+            }
         }
+    }
+}
 
-        let size_part = parts[0];
-        let rate_part = parts[1];
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        let size_split = size_part.split('x').collect::<Vec<&str>>();
+    fn parse<P: TypedValueParser>(parser: P, value: &str) -> Result<P::Value, Error> {
+        parser.parse_ref(&Command::new("wlout"), None, OsStr::new(value))
+    }
 
-        if size_split.len() != 2 {
-            let mut error = Error::new(ErrorKind::ValueValidation).with_cmd(cmd);
-            error.insert(
-                ContextKind::InvalidArg,
-                ContextValue::String(arg.unwrap().to_string()),
-            );
+    #[test]
+    fn mode_is_width_height_and_rate() {
+        let mode = parse(DisplayModeParser {}, "2560x1440@144").unwrap();
+        assert_eq!(
+            mode,
+            HeadModeInput {
+                width: 2560,
+                height: 1440,
+                rate: 144,
+            }
+        );
+    }
 
-            return Err(error);
+    #[test]
+    fn malformed_mode_is_a_validation_error() {
+        for value in [
+            "ax1@60",
+            "1920xb@60",
+            "1920x1080@c",
+            "1920x1080",
+            "1920@60",
+            "",
+        ] {
+            let error = parse(DisplayModeParser {}, value).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::ValueValidation, "{}", value);
         }
+    }
 
-        Ok(HeadModeInput {
-            rate: rate_part
-                .parse::<i32>()
-                .expect("Rate should be a valid integer"),
-            width: size_split[0]
-                .parse::<i32>()
-                .expect("Width should be a valid integer"),
-            height: size_split[1]
-                .parse::<i32>()
-                .expect("Height should be a valid integer"),
-        })
+    #[test]
+    fn wait_for_a_mode() {
+        assert_eq!(
+            parse(WaitConditionParser {}, "mode=1920x1080@60").unwrap(),
+            WaitCondition::Mode(HeadModeInput {
+                width: 1920,
+                height: 1080,
+                rate: 60,
+            })
+        );
+    }
+
+    #[test]
+    fn wait_for_a_malformed_mode_is_a_validation_error() {
+        for value in ["mode=axb@60", "mode=1920x1080", "mode="] {
+            let error = parse(WaitConditionParser {}, value).unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::ValueValidation, "{}", value);
+        }
+
+        let error = parse(WaitConditionParser {}, "plugged").unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::InvalidValue);
     }
 }
@@ -0,0 +1,142 @@
+use crate::model::HeadState;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+/// Saved states of the displays turned off by `wlout only`, restored by `wlout all`.
+pub static ONLY_STATE_FILE: &str = "only";
+
+/// Settings of the displays turned off by `wlout power off`, restored by `wlout power on`.
+pub static POWER_STATE_FILE: &str = "power";
+
+/// The wlout directory under `$XDG_STATE_HOME`, falling back to `~/.local/state`.
+pub fn state_dir() -> PathBuf {
+    env::var_os("XDG_STATE_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/state")))
+        .unwrap_or_else(env::temp_dir)
+        .join("wlout")
+}
+
+pub fn load_head_states(file_name: &str) -> Vec<HeadState> {
+    let path = state_dir().join(file_name);
+
+    let Ok(content) = fs::read_to_string(&path) else {
+        return vec![];
+    };
+
+    content
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| match line.parse::<HeadState>() {
+            Ok(state) => Some(state),
+            Err(error) => {
+                eprintln!(
+                    "Ignoring invalid saved state in {}: {}",
+                    path.display(),
+                    error
+                );
+                None
+            }
+        })
+        .collect()
+}
+
+pub fn save_head_states(file_name: &str, states: &[HeadState]) -> io::Result<()> {
+    let dir = state_dir();
+    fs::create_dir_all(&dir)?;
+
+    let content: String = states.iter().map(|state| format!("{}\n", state)).collect();
+
+    fs::write(dir.join(file_name), content)
+}
+
+pub fn remove_head_states(file_name: &str) -> io::Result<()> {
+    match fs::remove_file(state_dir().join(file_name)) {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+        _ => Ok(()),
+    }
+}
+
+/// Adds states to the ones already saved, which are kept for the displays saved before.
+pub fn add_head_states(file_name: &str, states: &[HeadState]) -> io::Result<()> {
+    save_head_states(
+        file_name,
+        &merge_head_states(load_head_states(file_name), states),
+    )
+}
+
+/// The saved states followed by the new ones of the displays not saved yet, so that the oldest
+/// state of a display wins, eg. the layout from before a first `wlout only`.
+fn merge_head_states(mut saved: Vec<HeadState>, states: &[HeadState]) -> Vec<HeadState> {
+    for state in states {
+        if !saved.iter().any(|other| other.is_same_display(state)) {
+            saved.push(state.clone());
+        }
+    }
+
+    saved
+}
+
+/// Saves the state of a display, replacing the one previously saved for the same display.
+pub fn save_head_state(file_name: &str, state: &HeadState) -> io::Result<()> {
+    let mut states: Vec<HeadState> = load_head_states(file_name)
+        .into_iter()
+        .filter(|saved| !saved.is_same_display(state))
+        .collect();
+    states.push(state.clone());
+
+    save_head_states(file_name, &states)
+}
+
+pub fn remove_head_state(file_name: &str, state: &HeadState) -> io::Result<()> {
+    let states: Vec<HeadState> = load_head_states(file_name)
+        .into_iter()
+        .filter(|saved| !saved.is_same_display(state))
+        .collect();
+
+    save_head_states(file_name, &states)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(name: &str, position: (i32, i32)) -> HeadState {
+        HeadState {
+            name: String::from(name),
+            make: None,
+            model: None,
+            serial: None,
+            enabled: true,
+            mode: None,
+            refresh: None,
+            position: Some(position),
+            scale: Some(1.0),
+            transform: None,
+            adaptive_sync: None,
+        }
+    }
+
+    #[test]
+    fn second_only_keeps_the_displays_of_the_first() {
+        // `only DP-1` saved the three displays, `only DP-2` then turned off DP-1 moved to the origin
+        let saved = vec![
+            state("DP-1", (1920, 0)),
+            state("DP-2", (0, 0)),
+            state("HDMI-A-1", (3840, 0)),
+        ];
+        let merged = merge_head_states(saved.clone(), &[state("DP-1", (0, 0))]);
+
+        assert_eq!(merged, saved);
+    }
+
+    #[test]
+    fn new_displays_are_added() {
+        let merged = merge_head_states(vec![state("DP-1", (0, 0))], &[state("DP-2", (1920, 0))]);
+
+        assert_eq!(merged, [state("DP-1", (0, 0)), state("DP-2", (1920, 0))]);
+    }
+}
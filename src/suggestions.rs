@@ -0,0 +1,83 @@
+/// Names further away than this from the requested one are not worth suggesting.
+const MAX_NAME_DISTANCE: usize = 2;
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Levenshtein distance between two strings, ignoring case.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.to_lowercase().chars().collect();
+    let b: Vec<char> = b.to_lowercase().chars().collect();
+
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a_char) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != b_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+/// The candidates close to the requested name, closest first, eg. `DP-1` for `dp1`.
+/// A candidate starting with the requested name is always close, so that `hdmi` finds `HDMI-A-1`.
+pub fn closest_names<'a>(name: &str, candidates: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let lowercase_name = name.to_lowercase();
+
+    let mut close: Vec<(usize, &str)> = candidates
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, candidate)| {
+            *distance <= MAX_NAME_DISTANCE
+                || (!lowercase_name.is_empty()
+                    && candidate.to_lowercase().starts_with(&lowercase_name))
+        })
+        .collect();
+    close.sort();
+    close.truncate(MAX_SUGGESTIONS);
+
+    close
+        .into_iter()
+        .map(|(_, candidate)| candidate.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONNECTED: [&str; 3] = ["DP-1", "DP-2", "HDMI-A-1"];
+
+    #[test]
+    fn edit_distance_ignores_case() {
+        assert_eq!(edit_distance("dp-1", "DP-1"), 0);
+        assert_eq!(edit_distance("dp1", "DP-1"), 1);
+        assert_eq!(edit_distance("eDP-1", "DP-2"), 2);
+        assert_eq!(edit_distance("", "DP-1"), 4);
+    }
+
+    #[test]
+    fn missing_dash_suggests_the_display() {
+        assert_eq!(
+            closest_names("dp1", CONNECTED.into_iter()),
+            ["DP-1", "DP-2"]
+        );
+    }
+
+    #[test]
+    fn prefix_suggests_the_display() {
+        assert_eq!(closest_names("hdmi", CONNECTED.into_iter()), ["HDMI-A-1"]);
+    }
+
+    #[test]
+    fn unrelated_name_suggests_nothing() {
+        assert!(closest_names("VGA-3", CONNECTED.into_iter()).is_empty());
+        assert!(closest_names("", ["DP-1"].into_iter()).is_empty());
+    }
+
+    #[test]
+    fn suggestions_are_limited() {
+        let candidates = ["DP-1", "DP-2", "DP-3", "DP-4"];
+        assert_eq!(closest_names("DP-5", candidates.into_iter()).len(), 3);
+    }
+}
@@ -0,0 +1,280 @@
+// VESA Coordinated Video Timings (CVT 1.1), as implemented by the `cvt` utility and the X server.
+
+const CVT_H_GRANULARITY: i32 = 8;
+const CVT_MIN_V_PORCH: i32 = 3;
+const CVT_MIN_V_BPORCH: i32 = 6;
+const CVT_CLOCK_STEP_KHZ: i32 = 250;
+
+// Standard (CRT) blanking
+const CVT_HSYNC_PERCENTAGE: i32 = 8;
+const CVT_MIN_VSYNC_BP_US: f64 = 550.0;
+const CVT_C_PRIME: f64 = 30.0;
+const CVT_M_PRIME: f64 = 300.0;
+
+// Reduced blanking
+const CVT_RB_MIN_VBLANK_US: f64 = 460.0;
+const CVT_RB_H_SYNC: i32 = 32;
+const CVT_RB_H_BLANK: i32 = 160;
+const CVT_RB_VFPORCH: i32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Blanking {
+    Standard,
+    Reduced,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModeTiming {
+    pub blanking: Blanking,
+    pub requested_rate: i32,
+    pub pixel_clock_khz: i32,
+    pub h_display: i32,
+    pub h_sync_start: i32,
+    pub h_sync_end: i32,
+    pub h_total: i32,
+    pub v_display: i32,
+    pub v_sync_start: i32,
+    pub v_sync_end: i32,
+    pub v_total: i32,
+}
+
+impl ModeTiming {
+    pub fn pixel_clock_mhz(&self) -> f64 {
+        self.pixel_clock_khz as f64 / 1000.0
+    }
+
+    pub fn horizontal_frequency_khz(&self) -> f64 {
+        self.pixel_clock_khz as f64 / self.h_total as f64
+    }
+
+    /// The refresh rate actually obtained once the pixel clock is rounded to the clock step.
+    pub fn refresh_rate(&self) -> f64 {
+        self.pixel_clock_khz as f64 * 1000.0 / (self.h_total as f64 * self.v_total as f64)
+    }
+
+    /// X11 modeline, the format printed by `cvt` and accepted by most tools dealing with custom modes.
+    pub fn modeline(&self) -> String {
+        let (name_suffix, sync) = match self.blanking {
+            Blanking::Standard => ("", "-hsync +vsync"),
+            Blanking::Reduced => ("R", "+hsync -vsync"),
+        };
+
+        format!(
+            "Modeline \"{}x{}{}_{:.2}\" {:.2} {} {} {} {} {} {} {} {} {}",
+            self.h_display,
+            self.v_display,
+            name_suffix,
+            self.requested_rate as f64,
+            self.pixel_clock_mhz(),
+            self.h_display,
+            self.h_sync_start,
+            self.h_sync_end,
+            self.h_total,
+            self.v_display,
+            self.v_sync_start,
+            self.v_sync_end,
+            self.v_total,
+            sync
+        )
+    }
+}
+
+/// Vertical sync width, which CVT uses to encode the aspect ratio of the mode.
+fn v_sync_width(width: i32, height: i32) -> i32 {
+    let is_ratio = |w: i32, h: i32| height % h == 0 && height * w / h == width;
+
+    if is_ratio(4, 3) {
+        4
+    } else if is_ratio(16, 9) {
+        5
+    } else if is_ratio(16, 10) {
+        6
+    } else if is_ratio(5, 4) || is_ratio(15, 9) {
+        7
+    } else {
+        10
+    }
+}
+
+/// Highest refresh rate leaving room for the minimum vertical blanking of the standard timings,
+/// the reduced blanking one being shorter.
+pub fn max_rate() -> i32 {
+    (1_000_000.0 / CVT_MIN_VSYNC_BP_US).ceil() as i32 - 1
+}
+
+/// Checks that both timings can be computed for the refresh rate, they are meaningless otherwise.
+pub fn check_rate(rate: i32) -> Result<(), String> {
+    if rate <= 0 || rate > max_rate() {
+        return Err(format!(
+            "the refresh rate must be between 1 and {} Hz, the frame has to be longer than the vertical blanking",
+            max_rate()
+        ));
+    }
+
+    Ok(())
+}
+
+/// Checks that the size leaves at least one character cell of pixels once the width is rounded down to it.
+pub fn check_size(width: i32, height: i32) -> Result<(), String> {
+    if round_down(width, CVT_H_GRANULARITY) < CVT_H_GRANULARITY {
+        return Err(format!(
+            "the width must be at least {} pixels",
+            CVT_H_GRANULARITY
+        ));
+    }
+    if height <= 0 {
+        return Err(String::from("the height must be a positive number"));
+    }
+
+    Ok(())
+}
+
+fn round_down(value: i32, step: i32) -> i32 {
+    value - value % step
+}
+
+pub fn cvt(width: i32, height: i32, rate: i32) -> ModeTiming {
+    let h_display = round_down(width, CVT_H_GRANULARITY);
+    let v_display = height;
+    let v_sync = v_sync_width(h_display, v_display);
+
+    let h_period_us =
+        (1_000_000.0 / rate as f64 - CVT_MIN_VSYNC_BP_US) / (v_display + CVT_MIN_V_PORCH) as f64;
+
+    let v_sync_and_back_porch =
+        ((CVT_MIN_VSYNC_BP_US / h_period_us) as i32 + 1).max(v_sync + CVT_MIN_V_BPORCH);
+
+    let h_blank_percentage = (CVT_C_PRIME - CVT_M_PRIME * h_period_us / 1000.0).max(20.0);
+    let h_blank = round_down(
+        (h_display as f64 * h_blank_percentage / (100.0 - h_blank_percentage)) as i32,
+        2 * CVT_H_GRANULARITY,
+    );
+    let h_total = h_display + h_blank;
+
+    let h_sync_end = h_display + h_blank / 2;
+    let h_sync_width = round_down(h_total * CVT_HSYNC_PERCENTAGE / 100, CVT_H_GRANULARITY);
+    let v_sync_start = v_display + CVT_MIN_V_PORCH;
+
+    ModeTiming {
+        blanking: Blanking::Standard,
+        requested_rate: rate,
+        pixel_clock_khz: round_down(
+            (h_total as f64 * 1000.0 / h_period_us) as i32,
+            CVT_CLOCK_STEP_KHZ,
+        ),
+        h_display,
+        h_sync_start: h_sync_end - h_sync_width,
+        h_sync_end,
+        h_total,
+        v_display,
+        v_sync_start,
+        v_sync_end: v_sync_start + v_sync,
+        v_total: v_display + v_sync_and_back_porch + CVT_MIN_V_PORCH,
+    }
+}
+
+pub fn cvt_reduced_blanking(width: i32, height: i32, rate: i32) -> ModeTiming {
+    let h_display = round_down(width, CVT_H_GRANULARITY);
+    let v_display = height;
+    let v_sync = v_sync_width(h_display, v_display);
+
+    let h_period_us = (1_000_000.0 / rate as f64 - CVT_RB_MIN_VBLANK_US) / v_display as f64;
+
+    let v_blank_lines = ((CVT_RB_MIN_VBLANK_US / h_period_us) as i32 + 1)
+        .max(CVT_RB_VFPORCH + v_sync + CVT_MIN_V_BPORCH);
+
+    let h_total = h_display + CVT_RB_H_BLANK;
+    let h_sync_end = h_display + CVT_RB_H_BLANK / 2;
+    let v_sync_start = v_display + CVT_RB_VFPORCH;
+
+    ModeTiming {
+        blanking: Blanking::Reduced,
+        requested_rate: rate,
+        pixel_clock_khz: round_down(
+            (h_total as f64 * 1000.0 / h_period_us) as i32,
+            CVT_CLOCK_STEP_KHZ,
+        ),
+        h_display,
+        h_sync_start: h_sync_end - CVT_RB_H_SYNC,
+        h_sync_end,
+        h_total,
+        v_display,
+        v_sync_start,
+        v_sync_end: v_sync_start + v_sync,
+        v_total: v_display + v_blank_lines,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The modelines printed by `cvt 1920 1080 60` and `cvt -r 1920 1080 60`
+    #[test]
+    fn cvt_of_1080p_at_60() {
+        let timing = cvt(1920, 1080, 60);
+
+        assert_eq!(timing.pixel_clock_khz, 173000);
+        assert_eq!(
+            timing.modeline(),
+            "Modeline \"1920x1080_60.00\" 173.00 1920 2048 2248 2576 1080 1083 1088 1120 -hsync +vsync"
+        );
+    }
+
+    #[test]
+    fn cvt_reduced_blanking_of_1080p_at_60() {
+        let timing = cvt_reduced_blanking(1920, 1080, 60);
+
+        assert_eq!(timing.pixel_clock_khz, 138500);
+        assert_eq!(
+            timing.modeline(),
+            "Modeline \"1920x1080R_60.00\" 138.50 1920 1968 2000 2080 1080 1083 1088 1111 +hsync -vsync"
+        );
+    }
+
+    #[test]
+    fn rates_without_room_for_the_blanking_are_rejected() {
+        assert!(check_rate(0).is_err());
+        assert!(check_rate(-60).is_err());
+        assert!(check_rate(100000).is_err());
+        assert!(check_rate(max_rate() + 1).is_err());
+        assert!(check_rate(60).is_ok());
+    }
+
+    #[test]
+    fn sizes_without_a_character_cell_are_rejected() {
+        assert!(check_size(0, 0).is_err());
+        assert!(check_size(1, 1).is_err());
+        assert!(check_size(7, 1080).is_err());
+        assert!(check_size(-1920, 1080).is_err());
+        assert!(check_size(1920, 0).is_err());
+        assert!(check_size(1920, -1080).is_err());
+        assert!(check_size(8, 1).is_ok());
+    }
+
+    #[test]
+    fn smallest_size_gives_finite_timings() {
+        // The pixel clock of such a small mode can round down to 0, but nothing is divided by 0
+        for timing in [cvt(8, 1, 60), cvt_reduced_blanking(8, 1, 60)] {
+            assert!(timing.h_total > 0 && timing.v_total > 0, "{:?}", timing);
+            assert!(timing.pixel_clock_khz >= 0, "{:?}", timing);
+            assert!(timing.refresh_rate().is_finite(), "{:?}", timing);
+            assert!(
+                timing.horizontal_frequency_khz().is_finite(),
+                "{:?}",
+                timing
+            );
+        }
+    }
+
+    #[test]
+    fn highest_rate_gives_positive_timings() {
+        for timing in [
+            cvt(10, 10, max_rate()),
+            cvt_reduced_blanking(10, 10, max_rate()),
+        ] {
+            assert!(timing.pixel_clock_khz > 0, "{:?}", timing);
+            assert!(timing.refresh_rate() > 0.0, "{:?}", timing);
+        }
+    }
+}
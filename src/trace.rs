@@ -0,0 +1,177 @@
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+use wayland_client::backend::ObjectId;
+use wayland_client::protocol::{wl_callback, wl_registry};
+use wayland_client::{Proxy, WEnum};
+use wayland_protocols_wlr::output_management::v1::client::zwlr_output_configuration_v1::Event as ConfigurationEvent;
+use wayland_protocols_wlr::output_management::v1::client::zwlr_output_head_v1::Event as HeadEvent;
+use wayland_protocols_wlr::output_management::v1::client::zwlr_output_manager_v1::Event as ManagerEvent;
+use wayland_protocols_wlr::output_management::v1::client::zwlr_output_mode_v1::Event as ModeEvent;
+
+/// Environment variable turning the trace on: `1` or `stderr` to write it on stderr, otherwise the path of
+/// the file to append it to.
+pub static TRACE_ENV_VAR: &str = "WLOUT_LOG";
+
+/// Where the trace goes, nowhere until `init` is called.
+static TRACE: OnceLock<Mutex<Box<dyn Write + Send>>> = OnceLock::new();
+
+/// Turns the trace on, written to the file when one is given and on stderr otherwise.
+/// `WLOUT_LOG` turns it on as well when neither `--debug` nor `--log-file` is given.
+pub fn init(debug: bool, log_file: Option<&Path>) -> io::Result<()> {
+    let env_value = env::var(TRACE_ENV_VAR).unwrap_or_default();
+    let log_file = match (log_file, env_value.as_str()) {
+        (Some(path), _) => Some(path.to_path_buf()),
+        (None, "" | "0") if !debug => return Ok(()),
+        (None, "" | "0" | "1" | "stderr") => None,
+        (None, path) => Some(PathBuf::from(path)),
+    };
+
+    let writer: Box<dyn Write + Send> = match log_file {
+        Some(path) => Box::new(open_log_file(&path)?),
+        None => Box::new(io::stderr()),
+    };
+    let _ = TRACE.set(Mutex::new(writer));
+
+    Ok(())
+}
+
+fn open_log_file(path: &Path) -> io::Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|error| {
+            io::Error::new(
+                error.kind(),
+                format!("Unable to open the log file {}: {}", path.display(), error),
+            )
+        })
+}
+
+/// Logs an event received on the object, the description is only built when tracing.
+pub fn event(object: &ObjectId, describe: impl FnOnce() -> String) {
+    write_line("<-", object, describe);
+}
+
+/// Logs a request sent on the object, the description is only built when tracing.
+pub fn request(object: &ObjectId, describe: impl FnOnce() -> String) {
+    write_line("->", object, describe);
+}
+
+fn write_line(direction: &str, object: &ObjectId, describe: impl FnOnce() -> String) {
+    let Some(trace) = TRACE.get() else {
+        return;
+    };
+
+    let line = format!(
+        "[{}] {} {}.{}\n",
+        timestamp(),
+        direction,
+        object,
+        describe()
+    );
+    if let Ok(mut writer) = trace.lock() {
+        // The trace is only a diagnostic, failing to write it must not fail the command
+        let _ = writer.write_all(line.as_bytes());
+        let _ = writer.flush();
+    }
+}
+
+/// The current time as `HH:MM:SS.mmm` in UTC.
+fn timestamp() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let seconds = now.as_secs() % 86400;
+
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60,
+        now.subsec_millis()
+    )
+}
+
+/// An enum argument by its name, or its raw value when the compositor sent an unknown one.
+fn enum_value<T: std::fmt::Debug>(value: &WEnum<T>) -> String {
+    match value {
+        WEnum::Value(value) => format!("{:?}", value),
+        WEnum::Unknown(raw) => format!("unknown {}", raw),
+    }
+}
+
+pub fn describe_registry_event(event: &wl_registry::Event) -> String {
+    match event {
+        wl_registry::Event::Global {
+            name,
+            interface,
+            version,
+        } => format!("global({}, \"{}\", {})", name, interface, version),
+        wl_registry::Event::GlobalRemove { name } => format!("global_remove({})", name),
+        other => format!("{:?}", other),
+    }
+}
+
+pub fn describe_callback_event(event: &wl_callback::Event) -> String {
+    match event {
+        wl_callback::Event::Done { callback_data } => format!("done({})", callback_data),
+        other => format!("{:?}", other),
+    }
+}
+
+pub fn describe_configuration_event(event: &ConfigurationEvent) -> String {
+    match event {
+        ConfigurationEvent::Succeeded => String::from("succeeded()"),
+        ConfigurationEvent::Failed => String::from("failed()"),
+        ConfigurationEvent::Cancelled => String::from("cancelled()"),
+        other => format!("{:?}", other),
+    }
+}
+
+pub fn describe_manager_event(event: &ManagerEvent) -> String {
+    match event {
+        ManagerEvent::Head { head } => format!("head(new {})", head.id()),
+        ManagerEvent::Done { serial } => format!("done({})", serial),
+        ManagerEvent::Finished => String::from("finished()"),
+        other => format!("{:?}", other),
+    }
+}
+
+pub fn describe_head_event(event: &HeadEvent) -> String {
+    match event {
+        HeadEvent::Name { name } => format!("name(\"{}\")", name),
+        HeadEvent::Description { description } => format!("description(\"{}\")", description),
+        HeadEvent::PhysicalSize { width, height } => {
+            format!("physical_size({}, {})", width, height)
+        }
+        HeadEvent::Mode { mode } => format!("mode(new {})", mode.id()),
+        HeadEvent::Enabled { enabled } => format!("enabled({})", enabled),
+        HeadEvent::CurrentMode { mode } => format!("current_mode({})", mode.id()),
+        HeadEvent::Position { x, y } => format!("position({}, {})", x, y),
+        HeadEvent::Transform { transform } => format!("transform({})", enum_value(transform)),
+        HeadEvent::Scale { scale } => format!("scale({})", scale),
+        HeadEvent::Finished => String::from("finished()"),
+        HeadEvent::Make { make } => format!("make(\"{}\")", make),
+        HeadEvent::Model { model } => format!("model(\"{}\")", model),
+        HeadEvent::SerialNumber { serial_number } => {
+            format!("serial_number(\"{}\")", serial_number)
+        }
+        HeadEvent::AdaptiveSync { state } => format!("adaptive_sync({})", enum_value(state)),
+        other => format!("{:?}", other),
+    }
+}
+
+pub fn describe_mode_event(event: &ModeEvent) -> String {
+    match event {
+        ModeEvent::Size { width, height } => format!("size({}, {})", width, height),
+        ModeEvent::Refresh { refresh } => format!("refresh({})", refresh),
+        ModeEvent::Preferred => String::from("preferred()"),
+        ModeEvent::Finished => String::from("finished()"),
+        other => format!("{:?}", other),
+    }
+}